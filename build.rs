@@ -0,0 +1,86 @@
+//! Records the commit the binaries are built from and the dfut they link, for
+//! `results::Metadata`. Read at run time, they would describe whatever the
+//! checkout holds then instead.
+
+use std::path::Path;
+use std::process::Command;
+
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .ok()?;
+    out.status
+        .success()
+        .then(|| String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+/// The version Cargo.lock resolved dfut to, with its source, or the commit of
+/// the checkout it's a path dependency on.
+fn dfut_version(manifest_dir: &Path) -> Option<String> {
+    let lock = std::fs::read_to_string(manifest_dir.join("Cargo.lock")).ok()?;
+    let lock: toml::Table = toml::from_str(&lock).ok()?;
+    let package = lock
+        .get("package")?
+        .as_array()?
+        .iter()
+        .find(|p| p.get("name").and_then(|n| n.as_str()) == Some("dfut"))?;
+    let version = package.get("version")?.as_str()?;
+    if let Some(source) = package.get("source").and_then(|s| s.as_str()) {
+        return Some(format!("{version} ({source})"));
+    }
+
+    let manifest = std::fs::read_to_string(manifest_dir.join("Cargo.toml")).ok()?;
+    let manifest: toml::Table = toml::from_str(&manifest).ok()?;
+    let path = manifest
+        .get("dependencies")
+        .and_then(|d| d.get("dfut"))
+        .and_then(|d| d.get("path"))
+        .and_then(|p| p.as_str());
+    let commit = path.and_then(|path| {
+        let dir = manifest_dir.join(path);
+        println!("cargo:rerun-if-changed={}", dir.display());
+        let commit = git(&dir, &["rev-parse", "HEAD"])?;
+        let dirty = git(&dir, &["status", "--porcelain", "."])?;
+        Some(if dirty.is_empty() {
+            commit
+        } else {
+            format!("{commit}-dirty")
+        })
+    });
+    Some(match commit {
+        Some(commit) => format!("{version} (path, {commit})"),
+        None => version.to_string(),
+    })
+}
+
+fn main() {
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let manifest_dir = Path::new(&manifest_dir);
+
+    for path in ["src", "tests", "build.rs", "Cargo.toml", "Cargo.lock"] {
+        println!("cargo:rerun-if-changed={path}");
+    }
+    if let Some(git_dir) = git(manifest_dir, &["rev-parse", "--absolute-git-dir"]) {
+        let git_dir = Path::new(&git_dir);
+        // Commits move HEAD or the branch it points to, staging changes the
+        // index.
+        println!("cargo:rerun-if-changed={}", git_dir.join("HEAD").display());
+        println!("cargo:rerun-if-changed={}", git_dir.join("index").display());
+        if let Some(head) = git(manifest_dir, &["symbolic-ref", "-q", "HEAD"]) {
+            println!("cargo:rerun-if-changed={}", git_dir.join(head).display());
+        }
+    }
+
+    if let Some(commit) = git(manifest_dir, &["rev-parse", "HEAD"]) {
+        println!("cargo:rustc-env=GIT_COMMIT={commit}");
+    }
+    if let Some(status) = git(manifest_dir, &["status", "--porcelain"]) {
+        println!("cargo:rustc-env=GIT_DIRTY={}", !status.is_empty());
+    }
+    if let Some(version) = dfut_version(manifest_dir) {
+        println!("cargo:rustc-env=DFUT_VERSION={version}");
+    }
+}
@@ -0,0 +1,860 @@
+use std::fmt;
+use std::future::Future;
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use dfut::{d_cancel, DFut, DResult};
+use hdrhistogram::Histogram;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rand_distr::{Distribution, Exp};
+use serde::de::{MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
+
+use crate::deadline::CallError;
+use crate::now;
+
+/// When a benchmark run stops issuing calls.
+#[derive(Debug, Clone, Copy)]
+pub enum Stop {
+    /// Each sender issues calls until the duration (after warmup) elapses.
+    Duration(Duration),
+    /// Each sender issues exactly this many measured calls.
+    Calls(u64),
+}
+
+/// Ordered key/value labels attached to every sample of a run, e.g. `size`
+/// or `exp_id`. They become columns in the CSV output.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Labels(Vec<(String, String)>);
+
+impl Labels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, key: &str, value: impl ToString) -> Self {
+        self.0.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(|(k, _)| k.as_str())
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(|(_, v)| v.as_str())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Only the labels with one of `keys`, in their current order.
+    pub fn select(&self, keys: &[String]) -> Self {
+        Self(
+            self.0
+                .iter()
+                .filter(|(k, _)| keys.contains(k))
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+/// `k=v` pairs separated by commas, e.g. `exp=20,n_workers=10`.
+impl fmt::Display for Labels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (k, v)) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{k}={v}")?;
+        }
+        Ok(())
+    }
+}
+
+// A JSON object in label order.
+impl Serialize for Labels {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (k, v) in &self.0 {
+            map.serialize_entry(k, v)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Labels {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct LabelsVisitor;
+
+        impl<'de> Visitor<'de> for LabelsVisitor {
+            type Value = Labels;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a map of labels")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Labels, A::Error> {
+                let mut labels = Labels::new();
+                while let Some((k, v)) = map.next_entry()? {
+                    labels.0.push((k, v));
+                }
+                Ok(labels)
+            }
+        }
+
+        deserializer.deserialize_map(LabelsVisitor)
+    }
+}
+
+/// Serializes a `Duration` as whole nanoseconds.
+mod nanos {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(d: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(d.as_nanos() as u64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        Ok(Duration::from_nanos(u64::deserialize(deserializer)?))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchCfg {
+    /// Calls completed before the warmup elapses are not recorded.
+    pub warmup: Duration,
+    pub stop: Stop,
+    pub labels: Labels,
+    /// Print progress every `log_every` calls per sender, 0 disables it.
+    pub log_every: u64,
+    /// Calls still running after this long are dropped and recorded as
+    /// timeouts, so a hung worker can't stall a sender. A call that has a
+    /// `Deadline` of its own should get `deadline::backstop` of it here, so
+    /// that the deadline passes first and the call can clean up.
+    pub timeout: Option<Duration>,
+    /// Senders stop issuing calls once this is cancelled, e.g. on Ctrl-C.
+    pub shutdown: Option<CancellationToken>,
+}
+
+impl Default for BenchCfg {
+    fn default() -> Self {
+        Self {
+            warmup: Duration::ZERO,
+            stop: Stop::Duration(Duration::from_secs(30)),
+            labels: Labels::new(),
+            log_every: 10,
+            timeout: None,
+            shutdown: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sample {
+    /// Wall clock time (since the unix epoch) at which the call completed.
+    #[serde(rename = "t_ns", with = "nanos")]
+    pub t: Duration,
+    pub labels: Labels,
+    #[serde(rename = "dur_ns", with = "nanos")]
+    pub dur: Duration,
+    /// `Debug` rendering of the error if the call failed.
+    pub error: Option<String>,
+    /// The call failed because a deadline passed, see [`Failure::is_timeout`].
+    pub timed_out: bool,
+}
+
+impl Sample {
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// The error of a benchmarked call.
+pub trait Failure: fmt::Debug {
+    fn is_timeout(&self) -> bool {
+        false
+    }
+
+    /// Cancelled calls are not recorded.
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+impl Failure for dfut::Error {}
+
+impl Failure for std::convert::Infallible {}
+
+impl Failure for CallError {
+    fn is_timeout(&self) -> bool {
+        matches!(self, CallError::Timeout)
+    }
+
+    fn is_cancelled(&self) -> bool {
+        matches!(self, CallError::Cancelled)
+    }
+}
+
+/// How the calls of an open-loop run are spaced, see [`run_open_loop`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Arrivals {
+    /// Exactly `1 / rate` apart.
+    Constant,
+    /// Exponentially distributed gaps with a mean of `1 / rate`, as if many
+    /// independent clients were sending.
+    #[default]
+    Poisson,
+}
+
+impl Arrivals {
+    pub const ALL: &'static [Arrivals] = &[Arrivals::Constant, Arrivals::Poisson];
+}
+
+impl fmt::Display for Arrivals {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Arrivals::Constant => "constant",
+            Arrivals::Poisson => "poisson",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for Arrivals {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Arrivals::ALL
+            .iter()
+            .find(|a| a.to_string() == s)
+            .copied()
+            .ok_or_else(|| format!("unknown arrivals: {s}"))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OpenLoop {
+    /// Target calls per second across all senders.
+    pub rate: f64,
+    pub arrivals: Arrivals,
+    /// Calls started but not completed at most. Once reached, further calls
+    /// wait to start, and the wait counts towards their latency.
+    pub max_in_flight: usize,
+    /// Seeds the Poisson arrivals.
+    pub seed: u64,
+}
+
+impl Default for OpenLoop {
+    fn default() -> Self {
+        Self {
+            rate: 1_000.,
+            arrivals: Arrivals::default(),
+            max_in_flight: 10_000,
+            seed: 0,
+        }
+    }
+}
+
+impl OpenLoop {
+    /// Offsets from the start of the run at which calls are due.
+    fn send_times(&self) -> impl Iterator<Item = Duration> {
+        assert!(
+            self.rate > 0. && self.rate.is_finite(),
+            "rate must be positive"
+        );
+        let arrivals = self.arrivals;
+        let mean = 1. / self.rate;
+        let exp = Exp::new(self.rate).unwrap();
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut t = 0.;
+        std::iter::from_fn(move || {
+            let due = Duration::from_secs_f64(t);
+            t += match arrivals {
+                Arrivals::Constant => mean,
+                Arrivals::Poisson => exp.sample(&mut rng),
+            };
+            Some(due)
+        })
+    }
+}
+
+// Open-loop flags of the no-op drivers. (Not a doc comment, clap would use it
+// as the about text of every binary.)
+#[derive(clap::Args, Debug, Clone, Serialize)]
+pub struct OpenLoopArgs {
+    /// Start this many calls per second regardless of whether earlier calls
+    /// completed, instead of one call per sender at a time.
+    #[arg(long)]
+    pub rate: Option<f64>,
+
+    /// Spacing of the calls of `--rate`: constant or poisson.
+    #[arg(long, default_value_t = Arrivals::default())]
+    pub arrivals: Arrivals,
+
+    /// Calls of `--rate` in flight at most.
+    #[arg(long, default_value_t = OpenLoop::default().max_in_flight)]
+    pub max_in_flight: usize,
+
+    #[arg(long, default_value_t = 0)]
+    pub arrivals_seed: u64,
+}
+
+impl OpenLoopArgs {
+    /// The open-loop config for `rate`, e.g. `--rate` or a point of a sweep.
+    pub fn at(&self, rate: f64) -> OpenLoop {
+        OpenLoop {
+            rate,
+            arrivals: self.arrivals,
+            max_in_flight: self.max_in_flight,
+            seed: self.arrivals_seed,
+        }
+    }
+
+    /// `None` for a closed-loop run.
+    pub fn load(&self) -> Option<OpenLoop> {
+        self.rate.map(|rate| self.at(rate))
+    }
+}
+
+/// The error of a failed call rendered with `Debug`, whether it timed out and
+/// whether it was cancelled.
+type CallFailure = (String, bool, bool);
+
+/// Awaits `call`, dropping it after `timeout`.
+async fn timed_call<Fut, O, E>(timeout: Option<Duration>, call: Fut) -> Result<O, CallFailure>
+where
+    Fut: Future<Output = Result<O, E>>,
+    E: Failure,
+{
+    let call = async {
+        call.await
+            .map_err(|e| (format!("{e:?}"), e.is_timeout(), e.is_cancelled()))
+    };
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, call)
+            .await
+            .unwrap_or_else(|_| Err((format!("{:?}", CallError::Timeout), true, false))),
+        None => call.await,
+    }
+}
+
+/// The sample of a call that completed now, `None` if it was cancelled.
+fn sample(labels: &Labels, dur: Duration, result: Result<(), CallFailure>) -> Option<Sample> {
+    let (error, timed_out) = match result {
+        Ok(()) => (None, false),
+        Err((_, _, true)) => return None,
+        Err((error, timed_out, _)) => (Some(error), timed_out),
+    };
+    Some(Sample {
+        t: now(),
+        labels: labels.clone(),
+        dur,
+        error,
+        timed_out,
+    })
+}
+
+/// Tracks the `DFut`s of a driver between submission and `d_await`, so the
+/// ones not awaited yet at shutdown are cancelled instead of leaving their
+/// results on the workers.
+#[derive(Debug, Default)]
+pub struct InFlight {
+    shutdown: CancellationToken,
+    completed: AtomicU64,
+    cancelled: AtomicU64,
+}
+
+impl InFlight {
+    pub fn new(shutdown: CancellationToken) -> Self {
+        Self {
+            shutdown,
+            ..Default::default()
+        }
+    }
+
+    /// Awaits `fut` with `d_await`, or cancels it with `d_cancel!` and returns
+    /// `CallError::Cancelled` if shutdown started. A `d_await` that is running
+    /// when shutdown starts isn't waited for. It holds the only handle to
+    /// `fut`, so it keeps running on a task of its own to collect the result
+    /// from the worker.
+    pub async fn d_await<T, F, Fut>(&self, fut: DFut<T>, d_await: F) -> Result<T, CallError>
+    where
+        T: Send + 'static,
+        F: FnOnce(DFut<T>) -> Fut,
+        Fut: Future<Output = DResult<T>> + Send + 'static,
+    {
+        if self.shutdown.is_cancelled() {
+            d_cancel!(fut);
+            self.cancelled.fetch_add(1, Ordering::Relaxed);
+            return Err(CallError::Cancelled);
+        }
+        let mut d_await = tokio::spawn(d_await(fut));
+        let v = tokio::select! {
+            v = &mut d_await => v.expect("d_await panicked")?,
+            () = self.shutdown.cancelled() => {
+                self.cancelled.fetch_add(1, Ordering::Relaxed);
+                return Err(CallError::Cancelled);
+            }
+        };
+        self.completed.fetch_add(1, Ordering::Relaxed);
+        Ok(v)
+    }
+
+    pub fn completed(&self) -> u64 {
+        self.completed.load(Ordering::Relaxed)
+    }
+
+    pub fn cancelled(&self) -> u64 {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Display for InFlight {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "completed={} cancelled={}",
+            self.completed(),
+            self.cancelled()
+        )
+    }
+}
+
+/// Runs `call` in a closed loop on one task per sender until `cfg.stop` is
+/// reached or `cfg.shutdown` is cancelled and returns the samples of all
+/// senders. Failed calls are recorded and count towards `Stop::Calls`.
+pub async fn run<S, F, Fut, E>(cfg: &BenchCfg, senders: Vec<S>, call: F) -> Vec<Sample>
+where
+    S: Send + Sync + 'static,
+    F: Fn(Arc<S>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<(), E>> + Send,
+    E: Failure,
+{
+    run_with_setup(cfg, senders, || (), move |s, ()| call(s), |()| ()).await
+}
+
+/// Like [`run`], but `setup` produces the input of every call and `verify`
+/// checks the output of every successful one, both outside of the timed
+/// region (e.g. generating and shuffling data to sort, then checking that it
+/// came back sorted).
+pub async fn run_with_setup<S, I, G, F, Fut, O, E, V>(
+    cfg: &BenchCfg,
+    senders: Vec<S>,
+    setup: G,
+    call: F,
+    verify: V,
+) -> Vec<Sample>
+where
+    S: Send + Sync + 'static,
+    I: Send,
+    G: Fn() -> I + Send + Sync + 'static,
+    F: Fn(Arc<S>, I) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<O, E>> + Send,
+    O: Send,
+    E: Failure,
+    V: Fn(O) + Send + Sync + 'static,
+{
+    let setup = Arc::new(setup);
+    let call = Arc::new(call);
+    let verify = Arc::new(verify);
+    let start = Instant::now();
+    let warmup = cfg.warmup;
+    let deadline = match cfg.stop {
+        Stop::Duration(duration) => Some(start + warmup + duration),
+        Stop::Calls(_) => None,
+    };
+
+    let mut js = tokio::task::JoinSet::new();
+    for (id, sender) in senders.into_iter().enumerate() {
+        js.spawn({
+            let sender = Arc::new(sender);
+            let setup = Arc::clone(&setup);
+            let call = Arc::clone(&call);
+            let verify = Arc::clone(&verify);
+            let cfg = cfg.clone();
+
+            async move {
+                let mut data = Vec::new();
+                for i in 0.. {
+                    // Calls that complete without waiting never yield, which
+                    // would starve e.g. the task cancelling `cfg.shutdown`.
+                    tokio::task::consume_budget().await;
+                    if cfg.shutdown.as_ref().is_some_and(|s| s.is_cancelled()) {
+                        break;
+                    }
+                    if let Stop::Calls(n_calls) = cfg.stop {
+                        if data.len() as u64 >= n_calls {
+                            break;
+                        }
+                    }
+
+                    let input = setup();
+                    let call_start = Instant::now();
+                    let result = timed_call(cfg.timeout, call(Arc::clone(&sender), input)).await;
+                    let dur = call_start.elapsed();
+                    let result = result.map(|output| verify(output));
+
+                    if start.elapsed() >= warmup {
+                        match sample(&cfg.labels, dur, result) {
+                            Some(sample) => data.push(sample),
+                            None => continue,
+                        }
+                    }
+
+                    if cfg.log_every != 0 && i % cfg.log_every == 0 {
+                        println!("{id}: {i}");
+                    }
+                    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                        break;
+                    }
+                }
+                data
+            }
+        });
+    }
+
+    let mut data = Vec::new();
+    while let Some(d) = js.join_next().await {
+        data.append(&mut d.unwrap());
+    }
+    data
+}
+
+/// The resolution of tokio's timers.
+const TIMER_TICK: Duration = Duration::from_millis(1);
+
+/// Starts `call` at the rate of `open_loop` regardless of whether earlier
+/// calls completed, cycling through `senders`, until `cfg.stop` is reached or
+/// `cfg.shutdown` is cancelled. Then waits for the calls in flight and returns
+/// their samples.
+///
+/// Unlike [`run`], latency is measured from when a call was due, not from
+/// when it started, so time spent queueing behind a saturated cluster (or
+/// behind `open_loop.max_in_flight`) is not omitted. Calls may start up to a
+/// timer tick early, their latency then counts from the start. `Stop::Calls` counts the
+/// calls started after the warmup.
+pub async fn run_open_loop<S, F, Fut, E>(
+    cfg: &BenchCfg,
+    open_loop: &OpenLoop,
+    senders: Vec<S>,
+    call: F,
+) -> Vec<Sample>
+where
+    S: Send + Sync + 'static,
+    F: Fn(Arc<S>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<(), E>> + Send,
+    E: Failure,
+{
+    assert!(!senders.is_empty(), "no senders");
+    let senders: Vec<_> = senders.into_iter().map(Arc::new).collect();
+    let call = Arc::new(call);
+    let permits = Arc::new(Semaphore::new(open_loop.max_in_flight));
+    let start = tokio::time::Instant::now();
+    let warmup = start + cfg.warmup;
+
+    let mut js = tokio::task::JoinSet::new();
+    let mut n_measured = 0;
+    for (i, due) in (0u64..).zip(open_loop.send_times()) {
+        let due = start + due;
+        let done = match cfg.stop {
+            Stop::Duration(duration) => due >= warmup + duration,
+            Stop::Calls(n_calls) => n_measured >= n_calls,
+        };
+        if done {
+            break;
+        }
+        // Timers fire up to a tick late, which would count as latency.
+        let wake = due.checked_sub(TIMER_TICK).unwrap_or(due);
+        if let Some(shutdown) = &cfg.shutdown {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = tokio::time::sleep_until(wake) => {}
+            }
+        } else {
+            tokio::time::sleep_until(wake).await;
+        }
+        let measured = due >= warmup;
+        n_measured += measured as u64;
+
+        let permit = Arc::clone(&permits).acquire_owned().await.unwrap();
+        // From when the call was due, or started if it started early.
+        let from = due.min(tokio::time::Instant::now());
+        let sender = Arc::clone(&senders[i as usize % senders.len()]);
+        let call = Arc::clone(&call);
+        let (labels, timeout) = (cfg.labels.clone(), cfg.timeout);
+        js.spawn(async move {
+            let result = timed_call(timeout, call(sender)).await;
+            drop(permit);
+            let dur = from.elapsed();
+            sample(&labels, dur, result).filter(|_| measured)
+        });
+
+        if cfg.log_every != 0 && i % cfg.log_every == 0 {
+            println!("{i}: in_flight={}", js.len());
+        }
+    }
+
+    let mut data = Vec::new();
+    while let Some(sample) = js.join_next().await {
+        data.extend(sample.unwrap());
+    }
+    data
+}
+
+/// Writes samples as `t,<label keys...>,dur_s,error,timed_out` where `t` is in
+/// milliseconds since the unix epoch, `dur_s` is in seconds and `error` is empty
+/// for successful calls. All samples are expected to carry the same label keys
+/// as the first one.
+pub fn write_csv(path: impl AsRef<Path>, samples: &[Sample]) -> csv::Result<()> {
+    let mut wtr = csv::Writer::from_path(path)?;
+
+    let mut header = vec!["t"];
+    if let Some(s) = samples.first() {
+        header.extend(s.labels.keys());
+    }
+    header.extend(["dur_s", "error", "timed_out"]);
+    wtr.write_record(&header)?;
+
+    for s in samples {
+        let mut record = vec![s.t.as_millis().to_string()];
+        record.extend(s.labels.values().map(str::to_string));
+        record.push(s.dur.as_secs_f64().to_string());
+        record.push(s.error.clone().unwrap_or_default());
+        record.push(s.timed_out.to_string());
+        wtr.write_record(&record)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Writes one row per run, its labels followed by its [`Summary`], e.g. to
+/// tabulate a sweep. Durations are in seconds like in [`write_csv`].
+pub fn write_summary_csv(path: impl AsRef<Path>, runs: &[(Labels, Summary)]) -> csv::Result<()> {
+    let mut wtr = csv::Writer::from_path(path)?;
+
+    let mut header = Vec::new();
+    if let Some((labels, _)) = runs.first() {
+        header.extend(labels.keys());
+    }
+    header.extend([
+        "count",
+        "errors",
+        "timeouts",
+        "elapsed",
+        "throughput",
+        "p50",
+        "p90",
+        "p99",
+        "p999",
+        "max",
+    ]);
+    wtr.write_record(&header)?;
+
+    for (labels, summary) in runs {
+        let mut record: Vec<String> = labels.values().map(str::to_string).collect();
+        record.extend([
+            summary.count.to_string(),
+            summary.errors.to_string(),
+            summary.timeouts.to_string(),
+            summary.elapsed.as_secs_f64().to_string(),
+            summary.throughput().to_string(),
+        ]);
+        record.extend(
+            [
+                summary.p50,
+                summary.p90,
+                summary.p99,
+                summary.p999,
+                summary.max,
+            ]
+            .map(|d| d.as_secs_f64().to_string()),
+        );
+        wtr.write_record(&record)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Reads samples written by [`write_csv`]. The `error` and `timed_out` columns
+/// are optional.
+pub fn read_csv(path: impl AsRef<Path>) -> csv::Result<Vec<Sample>> {
+    let invalid = |msg: String| csv::Error::from(std::io::Error::new(ErrorKind::InvalidData, msg));
+
+    let mut rdr = csv::Reader::from_path(path)?;
+    let header = rdr.headers()?.clone();
+    let column = |name: &str| header.iter().position(|h| h == name);
+    let (Some(0), Some(dur)) = (column("t"), column("dur_s")) else {
+        return Err(invalid(format!(
+            "expected t,<labels>,dur_s columns, got {header:?}"
+        )));
+    };
+    let (error, timed_out) = (column("error"), column("timed_out"));
+
+    let mut samples = Vec::new();
+    for record in rdr.records() {
+        let record = record?;
+        let field = |i: usize| record.get(i).unwrap_or_default();
+        let t: u64 = field(0)
+            .parse()
+            .map_err(|e| invalid(format!("t {:?}: {e}", field(0))))?;
+        let secs: f64 = field(dur)
+            .parse()
+            .map_err(|e| invalid(format!("dur_s {:?}: {e}", field(dur))))?;
+        let labels = (1..dur).fold(Labels::new(), |labels, i| labels.with(&header[i], field(i)));
+        samples.push(Sample {
+            t: Duration::from_millis(t),
+            labels,
+            dur: Duration::from_secs_f64(secs),
+            error: error
+                .map(field)
+                .filter(|e| !e.is_empty())
+                .map(str::to_string),
+            timed_out: timed_out.is_some_and(|i| field(i) == "true"),
+        });
+    }
+    Ok(samples)
+}
+
+/// Records the latencies of the successful `samples` in nanoseconds.
+pub fn histogram(samples: &[Sample]) -> Histogram<u64> {
+    let mut hist = Histogram::new(3).unwrap();
+    for s in samples.iter().filter(|s| s.is_ok()) {
+        hist.record(s.dur.as_nanos() as u64).unwrap();
+    }
+    hist
+}
+
+/// Latency percentiles and throughput of the successful calls of a run.
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub count: u64,
+    /// Failed calls, including timeouts.
+    pub errors: u64,
+    pub timeouts: u64,
+    /// From the start of the first measured call to the end of the last one.
+    pub elapsed: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub p999: Duration,
+    pub max: Duration,
+}
+
+impl Summary {
+    pub fn new(samples: &[Sample]) -> Self {
+        let hist = histogram(samples);
+        let first = samples.iter().map(|s| s.t.saturating_sub(s.dur)).min();
+        let last = samples.iter().map(|s| s.t).max();
+        let elapsed = match (first, last) {
+            (Some(first), Some(last)) => last - first,
+            _ => Duration::ZERO,
+        };
+        let at = |q| Duration::from_nanos(hist.value_at_quantile(q));
+        Self {
+            count: hist.len(),
+            errors: samples.iter().filter(|s| !s.is_ok()).count() as u64,
+            timeouts: samples.iter().filter(|s| s.timed_out).count() as u64,
+            elapsed,
+            p50: at(0.5),
+            p90: at(0.9),
+            p99: at(0.99),
+            p999: at(0.999),
+            max: Duration::from_nanos(hist.max()),
+        }
+    }
+
+    /// Completed calls per second.
+    pub fn throughput(&self) -> f64 {
+        if self.elapsed.is_zero() {
+            return 0.;
+        }
+        self.count as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "count={} errors={} timeouts={} elapsed={:?} throughput={:.2}/s",
+            self.count,
+            self.errors,
+            self.timeouts,
+            self.elapsed,
+            self.throughput()
+        )?;
+        write!(
+            f,
+            "p50={:?} p90={:?} p99={:?} p99.9={:?} max={:?}",
+            self.p50, self.p90, self.p99, self.p999, self.max
+        )
+    }
+}
+
+/// Writes the percentile distribution of `hist` in the HdrHistogram text
+/// format (`.hgrm`) with values in microseconds, so it can be loaded by the
+/// usual HdrHistogram plotters.
+pub fn write_histogram(path: impl AsRef<Path>, hist: &Histogram<u64>) -> std::io::Result<()> {
+    let mut w = std::io::BufWriter::new(std::fs::File::create(path)?);
+    writeln!(
+        w,
+        "{:>12} {:>14} {:>10} {:>14}",
+        "Value", "Percentile", "TotalCount", "1/(1-Percentile)"
+    )?;
+    let mut total = 0;
+    for v in hist.iter_quantiles(5) {
+        total += v.count_since_last_iteration();
+        let q = v.quantile_iterated_to();
+        let value = v.value_iterated_to() as f64 / 1_000.;
+        if q < 1. {
+            writeln!(
+                w,
+                "{value:12.3} {q:14.12} {total:10} {:14.2}",
+                1. / (1. - q)
+            )?;
+        } else {
+            writeln!(w, "{value:12.3} {q:14.12} {total:10}")?;
+        }
+    }
+    writeln!(
+        w,
+        "#[Mean    = {:12.3}, StdDeviation   = {:12.3}]",
+        hist.mean() / 1_000.,
+        hist.stdev() / 1_000.
+    )?;
+    writeln!(
+        w,
+        "#[Max     = {:12.3}, Total count    = {:12}]",
+        hist.max() as f64 / 1_000.,
+        hist.len()
+    )?;
+    w.flush()
+}
+
+/// Prints the [`Summary`] of `samples` and, if `histogram_path` is set, writes
+/// their latency histogram there.
+pub fn report(samples: &[Sample], histogram_path: Option<&Path>) -> std::io::Result<()> {
+    println!();
+    println!("summary");
+    println!("{}", Summary::new(samples));
+    if let Some(path) = histogram_path {
+        write_histogram(path, &histogram(samples))?;
+    }
+    Ok(())
+}
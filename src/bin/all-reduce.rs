@@ -1,91 +1,119 @@
-use dfut::{
-    d_await, d_cancel, into_dfut, DFut, DResult, GlobalScheduler, GlobalSchedulerCfg, Runtime,
-    WorkerServerConfig,
-};
-
-#[derive(Debug, Clone)]
-pub struct Worker {
-    runtime: Runtime,
-}
+use std::path::PathBuf;
+use std::time::Duration;
 
-#[into_dfut]
-impl Worker {
-    pub async fn all_reduce(&self, v: Vec<f64>) -> DResult<Vec<f64>> {
-        let mut f = Vec::new();
-        for chunk in v.chunks(1000) {
-            let tmp = self.do_work(chunk.to_vec()).await?;
-            f.push(self.reduce(tmp).await?);
-        }
+use clap::Parser;
+use rand::Rng;
+use serde::Serialize;
 
-        let n = f.len();
-        let f = self.shuffle(f).await?;
+use dfut_example::bench::{self, BenchCfg, Labels, Stop};
+use dfut_example::patterns::all_reduce::{Algorithm, ReduceOp, Worker, WorkerRootClient};
+use dfut_example::results::Results;
+use dfut_example::topology::TopologyArgs;
+use dfut_example::{now, ready};
 
-        let fs = self.runtime.share_n(&f, n as u64).await?;
+#[derive(Parser, Debug, Serialize)]
+struct Args {
+    #[command(flatten)]
+    topology: TopologyArgs,
 
-        let mut out = Vec::new();
-        for v in fs {
-            out.push(d_await!(self.do_work2(v).await?));
-        }
-        d_cancel!(f);
-        Ok(out)
-    }
+    /// Participants in each all-reduce, defaults to the number of workers.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    n_participants: Option<u64>,
 
-    pub async fn do_work(&self, v: Vec<f64>) -> DResult<Vec<f64>> {
-        Ok(v)
-    }
+    /// Elements of each participant's vector.
+    #[arg(long, default_value_t = 1_000_000)]
+    len: usize,
 
-    pub async fn reduce(&self, v: DFut<Vec<f64>>) -> DResult<f64> {
-        let mut sum = 0.;
-        for v in d_await!(v) {
-            sum += v;
-        }
-        Ok(sum)
-    }
+    #[arg(long, value_delimiter = ',', default_value = "sum,min,max,mean")]
+    ops: Vec<ReduceOp>,
 
-    pub async fn shuffle(&self, v: Vec<DFut<f64>>) -> DResult<f64> {
-        let mut sum = 0.;
-        for v in v {
-            sum += d_await!(v);
-        }
-        Ok(sum)
-    }
+    #[arg(long, value_delimiter = ',', default_value = "tree,ring")]
+    algorithms: Vec<Algorithm>,
+
+    #[arg(long, default_value_t = 30)]
+    duration_secs: u64,
 
-    pub async fn do_work2(&self, v: DFut<f64>) -> DResult<f64> {
-        let v = d_await!(v);
-        Ok(v)
+    /// Write the latency histogram (.hgrm) to this path.
+    #[arg(long)]
+    histogram_path: Option<PathBuf>,
+}
+
+/// Checks every participant received the local reduction, up to rounding
+/// from the different combine order.
+fn assert_reduced(got: &[Vec<f64>], want: &[f64], n_participants: usize) {
+    assert_eq!(got.len(), n_participants);
+    for v in got {
+        assert_eq!(v.len(), want.len());
+        for (g, w) in v.iter().zip(want) {
+            assert!((g - w).abs() <= 1e-9 * w.abs().max(1.), "got {g}, want {w}");
+        }
     }
 }
 
 #[tokio::main]
 async fn main() {
+    let args = Args::parse();
+    let started_at = now();
+
     tracing_subscriber::fmt::init();
     let prometheus_handle = metrics_exporter_prometheus::PrometheusBuilder::new()
         .install_recorder()
         .unwrap();
 
-    let global_scheduler_address = "http://127.0.0.1:8220";
-
-    tokio::spawn(GlobalScheduler::serve_forever(GlobalSchedulerCfg {
-        address: global_scheduler_address.to_string(),
-        ..Default::default()
-    }));
+    let topology = args.topology.load();
 
-    (1..=9).for_each(|i| {
-        tokio::spawn(Worker::serve_forever(WorkerServerConfig {
-            local_server_address: format!("http://127.0.0.1:812{i}"),
-            global_scheduler_address: global_scheduler_address.to_string(),
-            ..Default::default()
-        }));
-    });
+    ready::start_in_process(&topology, Worker::serve_forever)
+        .await
+        .unwrap();
 
-    let root_client = WorkerRootClient::new(&global_scheduler_address, "unique-id").await;
-    let client = root_client.new_client();
+    let root_client = WorkerRootClient::new(&topology.global_scheduler_address, "unique-id").await;
+
+    let n_participants = args.n_participants.unwrap_or(topology.n_workers) as usize;
+    let len = args.len;
+
+    let mut data = Vec::new();
+    for &op in &args.ops {
+        for &algorithm in &args.algorithms {
+            let cfg = BenchCfg {
+                stop: Stop::Duration(Duration::from_secs(args.duration_secs)),
+                labels: Labels::new()
+                    .with("n_workers", topology.n_workers)
+                    .with("n_participants", n_participants)
+                    .with("len", len)
+                    .with("op", op)
+                    .with("algorithm", algorithm),
+                log_every: 0,
+                ..Default::default()
+            };
+
+            data.extend(
+                bench::run_with_setup(
+                    &cfg,
+                    vec![root_client.new_client()],
+                    move || {
+                        let mut rng = rand::thread_rng();
+                        let inputs: Vec<Vec<f64>> = (0..n_participants)
+                            .map(|_| (0..len).map(|_| rng.gen_range(-1.0..1.0)).collect())
+                            .collect();
+                        let want = op.local(&inputs);
+                        (inputs, want)
+                    },
+                    move |client, (inputs, want)| async move {
+                        let f = client.all_reduce(inputs, op, algorithm).await?;
+                        Ok::<_, dfut::Error>((client.d_await(f).await?, want))
+                    },
+                    move |(got, want)| assert_reduced(&got, &want, n_participants),
+                )
+                .await,
+            );
+        }
+    }
 
-    let fut = client
-        .all_reduce((0..100_000_000).map(|v| v as f64).collect())
-        .await
+    bench::write_csv("all-reduce-data.csv", &data).unwrap();
+    bench::report(&data, args.histogram_path.as_deref()).unwrap();
+    Results::new(started_at, &args, &topology, data)
+        .write("all-reduce-data.json")
         .unwrap();
-    let _output = client.d_await(fut).await.unwrap();
 
     println!();
     println!("metrics");
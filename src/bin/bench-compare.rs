@@ -0,0 +1,97 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use dfut_example::compare::{self, CompareCfg};
+use dfut_example::results::Results;
+
+/// Compares the latencies and failure rates of two results files, e.g. before
+/// and after a dfut upgrade, and exits with status 1 if any regressed.
+#[derive(Parser, Debug)]
+struct Args {
+    baseline: PathBuf,
+
+    candidate: PathBuf,
+
+    /// Align samples by these labels, e.g. `exp,size`. Defaults to all labels.
+    #[arg(long, value_delimiter = ',')]
+    keys: Option<Vec<String>>,
+
+    #[arg(long, value_delimiter = ',', default_value = "0.5,0.99")]
+    quantiles: Vec<f64>,
+
+    /// A quantile regressed when its relative change is above this with
+    /// `--confidence`, e.g. 0.05 for 5%. An error or timeout rate regressed
+    /// when it rose by more than this.
+    #[arg(long, default_value_t = 0.05)]
+    threshold: f64,
+
+    #[arg(long, default_value_t = 0.95)]
+    confidence: f64,
+
+    #[arg(long, default_value_t = 1_000)]
+    n_resamples: usize,
+
+    /// Subsample larger groups to this many samples before bootstrapping.
+    #[arg(long, default_value_t = 10_000)]
+    max_samples: usize,
+
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+}
+
+fn load(path: &PathBuf) -> Results {
+    Results::load(path).unwrap_or_else(|e| panic!("failed to load {path:?}: {e}"))
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let baseline = load(&args.baseline);
+    let candidate = load(&args.candidate);
+    for (name, results) in [("baseline", &baseline), ("candidate", &candidate)] {
+        let metadata = &results.metadata;
+        println!(
+            "{name}: {} commit={} dfut={} host={} started_at_ms={}",
+            metadata.argv.join(" "),
+            metadata.git_commit.as_deref().unwrap_or("unknown"),
+            metadata.dfut_version.as_deref().unwrap_or("unknown"),
+            metadata.hostname.as_deref().unwrap_or("unknown"),
+            metadata.started_at_ms,
+        );
+    }
+    println!();
+
+    let cfg = CompareCfg {
+        keys: args.keys,
+        quantiles: args.quantiles,
+        n_resamples: args.n_resamples,
+        confidence: args.confidence,
+        max_samples: args.max_samples,
+        threshold: args.threshold,
+        seed: args.seed,
+    };
+    let comparison = compare::compare(&cfg, &baseline.samples, &candidate.samples);
+    for delta in &comparison.deltas {
+        println!("{delta}");
+    }
+    for delta in &comparison.failures {
+        println!("{delta}");
+    }
+    for labels in &comparison.only_baseline {
+        println!("{labels} only in baseline REGRESSION");
+    }
+    for labels in &comparison.only_candidate {
+        println!("{labels} only in candidate");
+    }
+
+    let n_regressions = comparison.n_regressions();
+    println!();
+    println!(
+        "{n_regressions} regressions at a threshold of {:.1}%",
+        cfg.threshold * 100.
+    );
+    if n_regressions > 0 {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,140 @@
+use std::path::PathBuf;
+use std::process::{ExitCode, Stdio};
+
+use clap::Parser;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::{Child, Command};
+
+use dfut_example::ready;
+use dfut_example::topology::{Topology, TopologyArgs};
+
+/// Runs a global scheduler, `n_workers` worker processes and a driver as
+/// child processes on free local ports. Arguments after `--` are passed to
+/// the driver, along with `--global-scheduler-address`. The scheduler and
+/// worker addresses of the topology are ignored.
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(flatten)]
+    topology: TopologyArgs,
+
+    #[arg(long, default_value = "no-op-worker")]
+    worker: String,
+
+    #[arg(long, default_value = "no-op-driver")]
+    driver: String,
+
+    #[arg(last = true)]
+    driver_args: Vec<String>,
+}
+
+/// Path of a binary built alongside this one.
+fn sibling_bin(name: &str) -> PathBuf {
+    std::env::current_exe().unwrap().with_file_name(name)
+}
+
+fn forward_lines(prefix: String, r: impl AsyncRead + Unpin + Send + 'static) {
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(r).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            println!("[{prefix}] {line}");
+        }
+    });
+}
+
+fn spawn(prefix: &str, bin: &str, args: &[String]) -> Child {
+    let mut child = Command::new(sibling_bin(bin))
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .unwrap_or_else(|e| panic!("failed to spawn {bin}: {e}"));
+    forward_lines(prefix.to_string(), child.stdout.take().unwrap());
+    forward_lines(prefix.to_string(), child.stderr.take().unwrap());
+    child
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+
+    tracing_subscriber::fmt::init();
+
+    let topology = args.topology.load_with_defaults(Topology {
+        n_workers: 4,
+        ..Default::default()
+    });
+    let topology = Topology {
+        heart_beat_timeout_secs: topology.heart_beat_timeout_secs,
+        ready_timeout_secs: topology.ready_timeout_secs,
+        ..Topology::ephemeral(topology.n_workers)
+    };
+    let global_scheduler_address = &topology.global_scheduler_address;
+
+    let mut scheduler_args = vec![
+        "--global-scheduler-address".to_string(),
+        global_scheduler_address.clone(),
+    ];
+    if let Some(secs) = topology.heart_beat_timeout_secs {
+        scheduler_args.extend(["--heart-beat-timeout-secs".to_string(), secs.to_string()]);
+    }
+
+    let mut children = Vec::new();
+    children.push(spawn(
+        "global-scheduler",
+        "global-scheduler",
+        &scheduler_args,
+    ));
+    ready::wait_for_scheduler(&topology).await.unwrap();
+
+    let worker_addresses = topology.worker_addresses();
+    for (i, local_server_address) in worker_addresses.iter().enumerate() {
+        children.push(spawn(
+            &format!("worker-{i}"),
+            &args.worker,
+            &[
+                "--global-scheduler-address".to_string(),
+                global_scheduler_address.clone(),
+                "--local-server-address".to_string(),
+                local_server_address.clone(),
+            ],
+        ));
+    }
+    ready::wait_for_workers_registered(&topology, topology.n_workers)
+        .await
+        .unwrap();
+    println!(
+        "cluster ready: global_scheduler_address={} workers={:?}",
+        global_scheduler_address, worker_addresses
+    );
+
+    let mut driver_args = vec![
+        "--global-scheduler-address".to_string(),
+        global_scheduler_address.clone(),
+    ];
+    driver_args.extend(args.driver_args);
+    let mut driver = spawn("driver", &args.driver, &driver_args);
+
+    let code = tokio::select! {
+        status = driver.wait() => {
+            let status = status.unwrap();
+            println!("driver exited: {status}");
+            if status.success() {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        _ = tokio::signal::ctrl_c() => {
+            println!("interrupted, shutting down");
+            driver.kill().await.unwrap();
+            ExitCode::FAILURE
+        }
+    };
+
+    for mut child in children.into_iter().rev() {
+        let _ = child.kill().await;
+    }
+
+    code
+}
@@ -1,10 +1,12 @@
 use clap::Parser;
-use dfut::{GlobalScheduler, GlobalSchedulerCfg};
+use dfut::GlobalScheduler;
+
+use dfut_example::topology::TopologyArgs;
 
 #[derive(Parser, Debug)]
 struct Args {
-    #[arg(short, long, default_value = "http://127.0.0.1:8220")]
-    address: String,
+    #[command(flatten)]
+    topology: TopologyArgs,
 }
 
 #[tokio::main]
@@ -13,9 +15,5 @@ async fn main() {
 
     tracing_subscriber::fmt::init();
 
-    GlobalScheduler::serve_forever(GlobalSchedulerCfg {
-        address: args.address,
-        ..Default::default()
-    })
-    .await;
+    GlobalScheduler::serve_forever(args.topology.load().global_scheduler_cfg()).await;
 }
@@ -1,133 +1,50 @@
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::{Duration, Instant};
+use std::time::Instant;
 
-use dfut::{
-    d_await, into_dfut, DFut, DResult, GlobalScheduler, GlobalSchedulerCfg, Runtime,
-    WorkerServerConfig,
-};
+use clap::Parser;
 use rand::seq::SliceRandom;
 
-static SUCCEED: AtomicBool = AtomicBool::new(false);
-
-pub fn partition(mut v: Vec<u64>) -> (Vec<u64>, u64, Vec<u64>) {
-    let p = v.pop().unwrap();
-    let mut l = Vec::new();
-    let mut g = Vec::new();
-    for e in v {
-        if e > p {
-            g.push(e);
-        } else {
-            l.push(e);
-        }
-    }
+use dfut_example::fault::{self, FaultArgs, MethodFaults};
+use dfut_example::patterns::basic::{Worker, WorkerRootClient};
+use dfut_example::patterns::sort::QuickSortCfg;
+use dfut_example::ready;
+use dfut_example::topology::TopologyArgs;
 
-    (l, p, g)
-}
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(flatten)]
+    topology: TopologyArgs,
 
-#[derive(Debug, Clone)]
-pub struct Worker {
-    runtime: Runtime,
-}
-
-#[into_dfut]
-impl Worker {
-    // Foo Bar.
-    pub async fn foo(&self, a: Vec<String>) -> DResult<String> {
-        Ok(a.join(" ").to_string())
-    }
-
-    pub async fn bar(&self, a: usize, b: DFut<String>) -> DResult<String> {
-        let b = d_await!(b);
-        Ok((b + " ").repeat(a).trim().to_string())
-    }
-
-    pub async fn foo_bar(&self, a: usize) -> DResult<String> {
-        let v = vec!["hello".to_string(), "world".to_string()];
-        let sf = self.foo(v).await?;
-        let b: DFut<String> = self.bar(a, sf).await?;
-        Ok(d_await!(b))
-    }
-
-    // Sort. Inspired by: https://docs.ray.io/en/latest/ray-core/patterns/nested-tasks.html.
-    pub async fn quick_sort(&self, mut v: Vec<u64>) -> DResult<Vec<u64>> {
-        if v.len() < 200_000 {
-            v.sort();
-            return Ok(v);
-        }
-        let (l, p, g) = partition(v);
-        let l_fut = self.quick_sort(l).await?;
-        let g_fut = self.quick_sort(g).await?;
-        let l = d_await!(l_fut);
-        let g = d_await!(g_fut);
-        let mut out = Vec::new();
-        out.extend(l);
-        out.push(p);
-        out.extend(g);
-        Ok(out)
-    }
-
-    // Supervisor. Inspired by:
-    // https://docs.ray.io/en/latest/ray-core/patterns/tree-of-actors.html.
-    pub async fn supervised_train(&self, hyperparam: f64, data: Vec<f64>) -> DResult<Vec<f64>> {
-        let mut v = Vec::new();
-        for d in data {
-            v.push(self.train(hyperparam, d).await?);
-        }
-
-        let mut o = Vec::new();
-        for f in v {
-            o.push(d_await!(f));
-        }
-
-        Ok(o)
-    }
-
-    pub async fn train(&self, hyperparam: f64, data: f64) -> DResult<f64> {
-        Ok(hyperparam * data)
-    }
-
-    pub async fn reconstruction(&self, v: u64) -> DResult<u64> {
-        // Since we don't retry from the driver and we don't retry on the
-        // current worker, we have the parent retry. We need one level of
-        // indirection.
-        let v = d_await!(self.retried_f(v).await?);
-        Ok(v)
-    }
-
-    pub async fn retried_f(&self, v: u64) -> DResult<u64> {
-        let succ = SUCCEED.fetch_or(true, Ordering::SeqCst);
-        if !succ {
-            return Err(dfut::Error::System);
-        }
-        Ok(2 * v)
-    }
+    #[command(flatten)]
+    faults: FaultArgs,
 }
 
 #[tokio::main]
 async fn main() {
+    let args = Args::parse();
+
     tracing_subscriber::fmt::init();
     let prometheus_handle = metrics_exporter_prometheus::PrometheusBuilder::new()
         .install_recorder()
         .unwrap();
 
-    let global_scheduler_address = "http://127.0.0.1:8120";
+    let topology = args.topology.load();
 
-    tokio::spawn(GlobalScheduler::serve_forever(GlobalSchedulerCfg {
-        address: global_scheduler_address.to_string(),
-        ..Default::default()
-    }));
-
-    tokio::time::sleep(Duration::from_secs(5)).await;
-
-    (1..=9).for_each(|i| {
-        tokio::spawn(Worker::serve_forever(WorkerServerConfig {
-            local_server_address: format!("http://127.0.0.1:812{i}"),
-            global_scheduler_address: global_scheduler_address.to_string(),
+    // Reconstruction needs `retried_f` to fail once.
+    let mut faults = args.faults.load();
+    faults
+        .methods
+        .entry("retried_f".to_string())
+        .or_insert(MethodFaults {
+            fail_first_n: 1,
             ..Default::default()
-        }));
-    });
+        });
+    fault::install(faults);
 
-    let root_client = WorkerRootClient::new(&global_scheduler_address, "unique-id").await;
+    ready::start_in_process(&topology, Worker::serve_forever)
+        .await
+        .unwrap();
+
+    let root_client = WorkerRootClient::new(&topology.global_scheduler_address, "unique-id").await;
     let client = root_client.new_client();
 
     // Foo Bar.
@@ -161,7 +78,10 @@ async fn main() {
             println!("local: took={elapsed:?}");
 
             let start = Instant::now();
-            let f = client.quick_sort(v.clone()).await.unwrap();
+            let f = client
+                .quick_sort(v.clone(), QuickSortCfg::default())
+                .await
+                .unwrap();
             let got = client.d_await(f).await.unwrap();
             let elapsed = start.elapsed();
             println!("distributed: took={elapsed:?}");
@@ -170,19 +90,6 @@ async fn main() {
         }
     }
 
-    // Supervisor.
-    {
-        let data = vec![1., 2., 3.];
-        let s1 = client.supervised_train(1., data.clone()).await.unwrap();
-        let s2 = client.supervised_train(2., data.clone()).await.unwrap();
-
-        let model1 = client.d_await(s1).await.unwrap();
-        assert_eq!(model1, vec![1., 2., 3.]);
-
-        let model2 = client.d_await(s2).await.unwrap();
-        assert_eq!(model2, vec![2., 4., 6.]);
-    }
-
     // Reconstruction.
     {
         let x = 42;
@@ -1,90 +1,144 @@
-use std::time::{Duration, Instant, SystemTime};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
 use clap::Parser;
-use csv::Writer;
-use tokio::time::sleep;
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
 
-use dfut_example::NoOpWorkerRootClient;
+use dfut_example::bench::{self, BenchCfg, InFlight, Labels, OpenLoopArgs, Stop};
+use dfut_example::deadline::{self, CallError, Deadline};
+use dfut_example::ready;
+use dfut_example::results::Results;
+use dfut_example::topology::TopologyArgs;
+use dfut_example::{now, NoOpWorkerClient, NoOpWorkerRootClient};
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Serialize)]
 struct Args {
-    #[arg(short, long, default_value = "http://127.0.0.1:8220")]
-    global_scheduler_address: String,
-
-    #[arg(short, long)]
-    n_workers: u64,
+    #[command(flatten)]
+    topology: TopologyArgs,
 
     #[arg(short, long)]
     exp: u64,
 
     #[arg(short, long)]
     n_calls: u64,
+
+    /// Also wait for the topology's workers to register before sending, not
+    /// only for the global scheduler. Not needed when started by `cluster`.
+    #[arg(long)]
+    wait_for_workers: bool,
+
+    /// Write the latency histogram (.hgrm) to this path.
+    #[arg(long)]
+    histogram_path: Option<PathBuf>,
+
+    /// Give up on calls after this many milliseconds. The deadline is passed
+    /// to the fan out, which cancels the `nop`s it still waits for.
+    #[arg(long)]
+    timeout_ms: Option<u64>,
+
+    // With `--rate`, `--n-calls` counts the calls of all senders.
+    #[command(flatten)]
+    open_loop: OpenLoopArgs,
 }
 
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
+    let started_at = now();
 
     tracing_subscriber::fmt::init();
     let prometheus_handle = metrics_exporter_prometheus::PrometheusBuilder::new()
         .install_recorder()
         .unwrap();
 
-    let n_tx = u64::max(args.n_workers / 5, 1);
+    let topology = args.topology.load();
+
+    ready::wait_for_scheduler(&topology).await.unwrap();
+    if args.wait_for_workers {
+        ready::wait_for_workers_registered(&topology, topology.n_workers)
+            .await
+            .unwrap();
+    }
 
-    let exp = args.exp;
+    let n_tx = u64::max(topology.n_workers / 5, 1);
 
-    let mut js = tokio::task::JoinSet::new();
+    let mut senders = Vec::new();
     for id in 0..n_tx {
-        js.spawn({
-            let root_client = NoOpWorkerRootClient::new(
-                &args.global_scheduler_address,
-                &format!("unique-id-{id}"),
-            )
-            .await;
+        let root_client = NoOpWorkerRootClient::new(
+            &topology.global_scheduler_address,
+            &format!("unique-id-{id}"),
+        )
+        .await;
+        let client = root_client.new_client();
+        senders.push((root_client, client));
+    }
+
+    // Cancelled on Ctrl-C or when the run ends.
+    let ct = CancellationToken::new();
+    tokio::spawn({
+        let ct = ct.clone();
+        async move {
+            tokio::signal::ctrl_c().await.unwrap();
+            println!("shutting down");
+            ct.cancel();
+        }
+    });
+    let in_flight = Arc::new(InFlight::new(ct.clone()));
+
+    let open_loop = args.open_loop.load();
+    let mut labels = Labels::new()
+        .with("exp", args.exp)
+        .with("n_workers", topology.n_workers);
+    if let Some(open_loop) = &open_loop {
+        labels = labels
+            .with("rate", open_loop.rate)
+            .with("arrivals", open_loop.arrivals);
+    }
 
+    let timeout = args.timeout_ms.map(Duration::from_millis);
+    let cfg = BenchCfg {
+        stop: Stop::Calls(args.n_calls),
+        labels,
+        timeout: deadline::backstop(timeout),
+        shutdown: Some(ct.clone()),
+        ..Default::default()
+    };
+    let a = 2 << args.exp;
+    let call = {
+        let in_flight = Arc::clone(&in_flight);
+        move |sender: Arc<(NoOpWorkerRootClient, NoOpWorkerClient)>| {
+            let in_flight = Arc::clone(&in_flight);
             async move {
-                // Wait for hbto to setup to avoid emitting retry errors for the demo.
-                sleep(Duration::from_secs(2)).await;
-
-                let client = root_client.new_client();
-
-                let mut data = Vec::new();
-                for i in 0..args.n_calls {
-                    let start = Instant::now();
-                    let fut = client.nop_fanout(5, 2 << exp).await.unwrap();
-
-                    let _output = client.d_await(fut).await.unwrap();
-                    let dur = start.elapsed();
-                    let when = SystemTime::now()
-                        .duration_since(SystemTime::UNIX_EPOCH)
-                        .unwrap();
-                    data.push((when, dur));
-
-                    if i % 10 == 0 {
-                        println!("{id}: {i}");
-                    }
-                }
-                data
+                let (_, client) = &*sender;
+                let deadline = timeout.map(Deadline::after);
+                let fut = deadline::within(deadline, client.nop_fanout(5, a, deadline)).await??;
+                // `nop_fanout` returns by the deadline on its own.
+                let d_await = move |fut| async move { sender.1.d_await(fut).await };
+                in_flight
+                    .d_await(fut, d_await)
+                    .await?
+                    .map_err(CallError::from)
             }
-        });
-    }
+        }
+    };
+    let data = match &open_loop {
+        Some(open_loop) => bench::run_open_loop(&cfg, open_loop, senders, call).await,
+        None => bench::run(&cfg, senders, call).await,
+    };
+    ct.cancel();
 
-    let mut data = Vec::new();
-    while let Some(d) = js.join_next().await {
-        let mut d = d.unwrap();
-        data.append(&mut d);
-    }
+    bench::write_csv(format!("no-op-data-{}.csv", topology.n_workers), &data).unwrap();
+
+    bench::report(&data, args.histogram_path.as_deref()).unwrap();
+    println!();
+    println!("shutdown");
+    println!("{in_flight}");
 
-    let mut wtr = Writer::from_path(format!("no-op-data-{}.csv", args.n_workers)).unwrap();
-    wtr.write_record(&["when", "dur"]).unwrap();
-    for (when, dur) in &data {
-        wtr.write_record(&[
-            format!("{}", when.as_millis()),
-            format!("{:?}", dur.as_millis()),
-        ])
+    Results::new(started_at, &args, &topology, data)
+        .write(format!("no-op-data-{}.json", topology.n_workers))
         .unwrap();
-    }
 
     println!();
     println!("metrics");
@@ -1,12 +1,11 @@
-use dfut::WorkerServerConfig;
-
 use clap::Parser;
+use dfut_example::topology::TopologyArgs;
 use dfut_example::NoOpWorker;
 
 #[derive(Parser, Debug)]
 struct Args {
-    #[arg(short, long, default_value = "http://127.0.0.1:8220")]
-    global_scheduler_address: String,
+    #[command(flatten)]
+    topology: TopologyArgs,
     #[arg(short, long)]
     local_server_address: String,
 }
@@ -20,10 +19,7 @@ async fn main() {
 
     let args = Args::parse();
 
-    NoOpWorker::serve_forever(WorkerServerConfig {
-        local_server_address: args.local_server_address.to_string(),
-        global_scheduler_address: args.global_scheduler_address.to_string(),
-        ..Default::default()
-    })
-    .await;
+    let topology = args.topology.load();
+
+    NoOpWorker::serve_forever(topology.worker_server_config(args.local_server_address)).await;
 }
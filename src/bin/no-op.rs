@@ -1,20 +1,25 @@
-use std::time::{Duration, Instant, SystemTime};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
 use clap::Parser;
-use csv::Writer;
-use tokio::time::sleep;
+use serde::Serialize;
 use tokio_util::sync::CancellationToken;
 
-use dfut::{GlobalScheduler, GlobalSchedulerCfg, WorkerServerConfig};
-use dfut_example::{NoOpWorker, NoOpWorkerRootClient};
+use dfut_example::bench::{
+    self, BenchCfg, InFlight, Labels, OpenLoop, OpenLoopArgs, Sample, Stop, Summary,
+};
+use dfut_example::chaos::{self, ChaosCfg};
+use dfut_example::deadline::{self, CallError, Deadline};
+use dfut_example::ready;
+use dfut_example::results::Results;
+use dfut_example::topology::{Topology, TopologyArgs};
+use dfut_example::{now, NoOpWorker, NoOpWorkerClient, NoOpWorkerRootClient};
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Serialize)]
 struct Args {
-    #[arg(short, long, default_value = "http://127.0.0.1:8220")]
-    global_scheduler_address: String,
-
-    #[arg(short, long, default_value_t = 10)]
-    n_workers: u64,
+    #[command(flatten)]
+    topology: TopologyArgs,
 
     #[arg(short, long, default_value_t = 20)]
     exp: u64,
@@ -22,109 +27,366 @@ struct Args {
     #[arg(short, long, default_value_t = 5)]
     fan_out_by: u64,
 
-    #[arg(short, long, default_value_t = 5)]
-    heart_beat_timeout_secs: u64,
+    #[arg(long, default_value_t = 0)]
+    warmup_secs: u64,
+
+    #[arg(long, default_value_t = 30)]
+    duration_secs: u64,
+
+    /// Write the latency histogram (.hgrm) to this path.
+    #[arg(long)]
+    histogram_path: Option<PathBuf>,
+
+    /// Give up on calls after this many milliseconds. The deadline is passed
+    /// to the fan out, which cancels the `nop`s it still waits for.
+    #[arg(long)]
+    timeout_ms: Option<u64>,
+
+    /// Kill a random worker every this many seconds.
+    #[arg(long)]
+    chaos_interval_secs: Option<u64>,
+
+    /// Restart killed workers after this many seconds.
+    #[arg(long, default_value_t = 5)]
+    chaos_downtime_secs: u64,
+
+    #[arg(long, default_value_t = 0)]
+    chaos_seed: u64,
+
+    #[command(flatten)]
+    open_loop: OpenLoopArgs,
+
+    /// Sweep every combination of these worker counts, `--sweep-exps`,
+    /// `--sweep-fan-out-bys` and `--sweep-rates`, restarting the in-process
+    /// cluster for each. Lists that aren't given sweep only `--n-workers`,
+    /// `--exp`, `--fan-out-by` or `--rate`.
+    #[arg(long, value_delimiter = ',', conflicts_with = "chaos_interval_secs")]
+    sweep_n_workers: Option<Vec<u64>>,
+
+    #[arg(long, value_delimiter = ',', conflicts_with = "chaos_interval_secs")]
+    sweep_exps: Option<Vec<u64>>,
+
+    #[arg(long, value_delimiter = ',', conflicts_with = "chaos_interval_secs")]
+    sweep_fan_out_bys: Option<Vec<u64>>,
+
+    /// Open-loop rates to sweep, e.g. to find the rate at which the cluster
+    /// saturates.
+    #[arg(long, value_delimiter = ',', conflicts_with = "chaos_interval_secs")]
+    sweep_rates: Option<Vec<f64>>,
+}
+
+/// One configuration of the benchmark. `rate` is unset for closed-loop runs.
+#[derive(Debug, Clone, Copy)]
+struct Point {
+    n_workers: u64,
+    exp: u64,
+    fan_out_by: u64,
+    rate: Option<f64>,
+}
+
+impl Args {
+    fn is_sweep(&self) -> bool {
+        self.sweep_n_workers.is_some()
+            || self.sweep_exps.is_some()
+            || self.sweep_fan_out_bys.is_some()
+            || self.sweep_rates.is_some()
+    }
+
+    fn open_loop(&self, point: &Point) -> Option<OpenLoop> {
+        point.rate.map(|rate| self.open_loop.at(rate))
+    }
+
+    /// Labels the samples of open-loop runs with their rate and arrivals.
+    fn with_open_loop(&self, labels: Labels, point: &Point) -> Labels {
+        match self.open_loop(point) {
+            Some(open_loop) => labels
+                .with("rate", open_loop.rate)
+                .with("arrivals", open_loop.arrivals),
+            None => labels,
+        }
+    }
+
+    fn sweep_points(&self, topology: &Topology) -> Vec<Point> {
+        let n_workers = self
+            .sweep_n_workers
+            .clone()
+            .unwrap_or(vec![topology.n_workers]);
+        let exps = self.sweep_exps.clone().unwrap_or(vec![self.exp]);
+        let fan_out_bys = self
+            .sweep_fan_out_bys
+            .clone()
+            .unwrap_or(vec![self.fan_out_by]);
+        let rates = match &self.sweep_rates {
+            Some(rates) => rates.iter().copied().map(Some).collect(),
+            None => vec![self.open_loop.rate],
+        };
+
+        let mut points = Vec::new();
+        for &n_workers in &n_workers {
+            for &exp in &exps {
+                for &fan_out_by in &fan_out_bys {
+                    for &rate in &rates {
+                        points.push(Point {
+                            n_workers,
+                            exp,
+                            fan_out_by,
+                            rate,
+                        });
+                    }
+                }
+            }
+        }
+        points
+    }
+}
+
+/// `topology` with only its first `n_workers` workers.
+fn with_n_workers(topology: &Topology, n_workers: u64) -> Topology {
+    let mut topology = topology.clone();
+    if !topology.workers.is_empty() {
+        assert!(
+            n_workers as usize <= topology.workers.len(),
+            "can't sweep {n_workers} workers, the topology lists {}",
+            topology.workers.len()
+        );
+        topology.workers.truncate(n_workers as usize);
+    }
+    topology.n_workers = n_workers;
+    topology
+}
+
+/// Calls `nop_fanout(fan_out_by, 2^exp)` from one sender per five workers,
+/// closed-loop or at the point's rate, until the run ends or `ct` is
+/// cancelled.
+async fn run(
+    args: &Args,
+    topology: &Topology,
+    labels: Labels,
+    point: Point,
+    in_flight: &Arc<InFlight>,
+    ct: &CancellationToken,
+) -> Vec<Sample> {
+    let n_senders = u64::max(topology.n_workers / 5, 1);
+
+    println!("Using n_senders={}", n_senders);
+
+    let mut senders = Vec::new();
+    for id in 0..n_senders {
+        let root_client = NoOpWorkerRootClient::new(
+            &topology.global_scheduler_address,
+            &format!("unique-id-{id}"),
+        )
+        .await;
+        let client = root_client.new_client();
+        senders.push((root_client, client));
+    }
+
+    let timeout = args.timeout_ms.map(Duration::from_millis);
+    let cfg = BenchCfg {
+        warmup: Duration::from_secs(args.warmup_secs),
+        stop: Stop::Duration(Duration::from_secs(args.duration_secs)),
+        labels,
+        timeout: deadline::backstop(timeout),
+        shutdown: Some(ct.clone()),
+        ..Default::default()
+    };
+
+    let (fan_out_by, a) = (point.fan_out_by, 1 << point.exp);
+    let call = {
+        let in_flight = Arc::clone(in_flight);
+        move |sender: Arc<(NoOpWorkerRootClient, NoOpWorkerClient)>| {
+            let in_flight = Arc::clone(&in_flight);
+            async move {
+                let (_, client) = &*sender;
+                let deadline = timeout.map(Deadline::after);
+                let fut = deadline::within(deadline, client.nop_fanout(fan_out_by, a, deadline))
+                    .await??;
+                // `nop_fanout` returns by the deadline on its own.
+                let d_await = move |fut| async move { sender.1.d_await(fut).await };
+                in_flight
+                    .d_await(fut, d_await)
+                    .await?
+                    .map_err(CallError::from)
+            }
+        }
+    };
+    match args.open_loop(&point) {
+        Some(open_loop) => bench::run_open_loop(&cfg, &open_loop, senders, call).await,
+        None => bench::run(&cfg, senders, call).await,
+    }
 }
 
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
+    let started_at = now();
 
     tracing_subscriber::fmt::init();
     let prometheus_handle = metrics_exporter_prometheus::PrometheusBuilder::new()
         .install_recorder()
         .unwrap();
 
-    tokio::spawn(GlobalScheduler::serve_forever(GlobalSchedulerCfg {
-        address: args.global_scheduler_address.to_string(),
-        heart_beat_timeout: Duration::from_secs(args.heart_beat_timeout_secs),
+    let topology = args.topology.load_with_defaults(Topology {
+        heart_beat_timeout_secs: Some(5),
         ..Default::default()
-    }));
-
-    let base_port_number = 8120;
-    (0..args.n_workers).for_each(|i| {
-        tokio::spawn({
-            let local_server_address = format!("http://127.0.0.1:{}", base_port_number + i);
-            let global_scheduler_address = args.global_scheduler_address.clone();
-            async move {
-                NoOpWorker::serve_forever(WorkerServerConfig {
-                    local_server_address,
-                    global_scheduler_address,
-                    ..Default::default()
-                })
-                .await;
-            }
-        });
     });
 
-    let n_senders = u64::max(args.n_workers / 5, 1);
-
-    println!("Using n_senders={}", n_senders);
+    // Cancelled on Ctrl-C or when the run ends.
     let ct = CancellationToken::new();
+    tokio::spawn({
+        let ct = ct.clone();
+        async move {
+            tokio::signal::ctrl_c().await.unwrap();
+            println!("shutting down");
+            ct.cancel();
+        }
+    });
+    let in_flight = Arc::new(InFlight::new(ct.clone()));
 
-    let mut js = tokio::task::JoinSet::new();
-    for id in 0..n_senders {
-        js.spawn({
-            let ct = ct.clone();
-            let root_client = NoOpWorkerRootClient::new(
-                &args.global_scheduler_address,
-                &format!("unique-id-{id}"),
-            )
-            .await;
-
-            async move {
-                // Wait for hbto to setup to avoid emitting retry errors for the demo.
-                sleep(Duration::from_secs(2)).await;
-                let client = root_client.new_client();
-
-                let mut data = Vec::new();
-                for i in 0.. {
-                    let start = Instant::now();
-                    let fut = client
-                        .nop_fanout(args.fan_out_by, 1 << args.exp)
-                        .await
-                        .unwrap();
-
-                    let _output = client.d_await(fut).await.unwrap();
-                    let dur = start.elapsed();
-                    let when = SystemTime::now()
-                        .duration_since(SystemTime::UNIX_EPOCH)
-                        .unwrap();
-                    data.push((when, dur));
-
-                    if i % 10 == 0 {
-                        println!("{id}: {i}");
-                    }
-                    if ct.is_cancelled() {
-                        break;
-                    }
-                }
-                data
-            }
-        });
+    if args.is_sweep() {
+        sweep(&args, started_at, &topology, &in_flight, &ct).await;
+        println!();
+        println!("metrics");
+        println!("{}", prometheus_handle.render());
+        return;
     }
 
-    sleep(Duration::from_secs(30)).await;
+    let workers = ready::start_in_process(&topology, NoOpWorker::serve_forever)
+        .await
+        .unwrap();
+
+    let chaos = args.chaos_interval_secs.map(|interval_secs| {
+        tokio::spawn(chaos::run(
+            ChaosCfg {
+                interval: Duration::from_secs(interval_secs),
+                downtime: Duration::from_secs(args.chaos_downtime_secs),
+                seed: args.chaos_seed,
+            },
+            topology.clone(),
+            workers,
+            NoOpWorker::serve_forever,
+            ct.clone(),
+        ))
+    });
+
+    let point = Point {
+        n_workers: topology.n_workers,
+        exp: args.exp,
+        fan_out_by: args.fan_out_by,
+        rate: args.open_loop.rate,
+    };
+    let labels = Labels::new()
+        .with("exp", args.exp)
+        .with("n_workers", topology.n_workers);
+    let labels = args.with_open_loop(labels, &point);
+    let data = run(&args, &topology, labels, point, &in_flight, &ct).await;
     ct.cancel();
 
-    let mut data = Vec::new();
-    while let Some(d) = js.join_next().await {
-        let mut d = d.unwrap();
-        data.append(&mut d);
-    }
+    bench::write_csv(
+        format!("no-op-data-{}-{}.csv", args.exp, topology.n_workers),
+        &data,
+    )
+    .unwrap();
+
+    bench::report(&data, args.histogram_path.as_deref()).unwrap();
+    println!();
+    println!("shutdown");
+    println!("{in_flight}");
 
-    let mut wtr =
-        Writer::from_path(format!("no-op-data-{}-{}.csv", args.exp, args.n_workers)).unwrap();
-    wtr.write_record(&["t", "dur"]).unwrap();
-    for (when, dur) in &data {
-        wtr.write_record(&[
-            format!("{}", when.as_millis()),
-            format!("{:?}", dur.as_millis()),
-        ])
+    if let Some(chaos) = chaos {
+        let recoveries = chaos::recoveries(&chaos.await.unwrap(), &data);
+        println!();
+        println!("chaos");
+        for r in &recoveries {
+            println!(
+                "worker={} errors={} recovered_after={:?}",
+                r.event.worker, r.errors, r.recovered_after
+            );
+        }
+        chaos::write_csv(
+            format!("no-op-chaos-{}-{}.csv", args.exp, topology.n_workers),
+            &recoveries,
+        )
         .unwrap();
     }
 
+    Results::new(started_at, &args, &topology, data)
+        .write(format!(
+            "no-op-data-{}-{}.json",
+            args.exp, topology.n_workers
+        ))
+        .unwrap();
+
     println!();
     println!("metrics");
     println!("{}", prometheus_handle.render());
 }
+
+/// Runs every point of the sweep on a fresh cluster and writes the samples of
+/// all of them to `no-op-sweep-data.{csv,json}` and one summary row per point
+/// to `no-op-sweep.csv`.
+async fn sweep(
+    args: &Args,
+    started_at: Duration,
+    topology: &Topology,
+    in_flight: &Arc<InFlight>,
+    ct: &CancellationToken,
+) {
+    let mut data = Vec::new();
+    let mut summaries = Vec::new();
+    for point in args.sweep_points(topology) {
+        if ct.is_cancelled() {
+            break;
+        }
+        let topology = with_n_workers(topology, point.n_workers);
+        let labels = Labels::new()
+            .with("exp", point.exp)
+            .with("n_workers", point.n_workers)
+            .with("fan_out_by", point.fan_out_by);
+        let labels = args.with_open_loop(labels, &point);
+        println!();
+        println!("{labels}");
+
+        let cluster = ready::start_cluster(&topology, NoOpWorker::serve_forever)
+            .await
+            .unwrap();
+        let samples = run(args, &topology, labels.clone(), point, in_flight, ct).await;
+        cluster.stop().await;
+
+        let summary = Summary::new(&samples);
+        println!("{summary}");
+        summaries.push((labels, summary));
+        data.extend(samples);
+    }
+    ct.cancel();
+
+    println!();
+    println!("sweep");
+    println!(
+        "{:>9} {:>4} {:>10} {:>10} {:>12} {:>12} {:>12} {:>7}",
+        "n_workers", "exp", "fan_out_by", "rate", "throughput/s", "p50", "p99", "errors"
+    );
+    for (labels, summary) in &summaries {
+        let label = |k| labels.get(k).unwrap_or_default();
+        println!(
+            "{:>9} {:>4} {:>10} {:>10} {:>12.2} {:>12} {:>12} {:>7}",
+            label("n_workers"),
+            label("exp"),
+            label("fan_out_by"),
+            labels.get("rate").unwrap_or("closed"),
+            summary.throughput(),
+            format!("{:?}", summary.p50),
+            format!("{:?}", summary.p99),
+            summary.errors,
+        );
+    }
+    println!();
+    println!("shutdown");
+    println!("{in_flight}");
+
+    bench::write_csv("no-op-sweep-data.csv", &data).unwrap();
+    bench::write_summary_csv("no-op-sweep.csv", &summaries).unwrap();
+    Results::new(started_at, args, topology, data)
+        .write("no-op-sweep-data.json")
+        .unwrap();
+}
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use clap::Parser;
+use serde::Serialize;
+
+use dfut_example::bench::{self, BenchCfg, Labels, Stop, Summary};
+use dfut_example::payload::{self, Payload, Shape};
+use dfut_example::results::Results;
+use dfut_example::topology::TopologyArgs;
+use dfut_example::{now, ready, NoOpWorker, NoOpWorkerRootClient};
+
+#[derive(Parser, Debug, Serialize)]
+struct Args {
+    #[command(flatten)]
+    topology: TopologyArgs,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "bytes,u64s,records,strings,d-fut"
+    )]
+    shapes: Vec<Shape>,
+
+    /// Payloads of about `2^exp` bytes.
+    #[arg(long, value_delimiter = ',', default_value = "4,10,14,18,20")]
+    exps: Vec<u32>,
+
+    /// Concurrent callers.
+    #[arg(long, default_value_t = 4)]
+    n_senders: usize,
+
+    #[arg(long, default_value_t = 5)]
+    duration_secs: u64,
+
+    /// Encode and decode each payload this many times to estimate its
+    /// serialization cost.
+    #[arg(long, default_value_t = 21)]
+    codec_tries: usize,
+}
+
+fn print_costs(name: &str, points: &[(f64, f64)]) {
+    match payload::costs(points) {
+        Some((per_call, per_byte)) => println!(
+            "  {name}: per_call={:?} per_byte={:.3}ns",
+            Duration::from_secs_f64(per_call),
+            per_byte * 1e9
+        ),
+        None => println!("  {name}: needs two payload sizes"),
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+    let started_at = now();
+
+    tracing_subscriber::fmt::init();
+
+    let topology = args.topology.load();
+
+    ready::start_in_process(&topology, NoOpWorker::serve_forever)
+        .await
+        .unwrap();
+
+    let root_client =
+        NoOpWorkerRootClient::new(&topology.global_scheduler_address, "unique-id").await;
+
+    let bench_cfg = |labels: &Labels| BenchCfg {
+        stop: Stop::Duration(Duration::from_secs(args.duration_secs)),
+        labels: labels.clone(),
+        log_every: 0,
+        ..Default::default()
+    };
+    let senders = || {
+        (0..args.n_senders)
+            .map(|_| root_client.new_client())
+            .collect::<Vec<_>>()
+    };
+
+    let mut data = Vec::new();
+    let mut summaries = Vec::new();
+
+    // `Shape::DFut` makes two calls, so its latency is also reported over
+    // that of a single call without payload.
+    let mut nop_p50 = None;
+    if args.shapes.contains(&Shape::DFut) {
+        let labels = Labels::new().with("shape", "nop").with("bytes", 0);
+        let samples = bench::run(&bench_cfg(&labels), senders(), |client| async move {
+            let f = client.nop(0).await?;
+            client.d_await(f).await?;
+            Ok::<_, dfut::Error>(())
+        })
+        .await;
+        let summary = Summary::new(&samples);
+        println!("{labels}");
+        println!("{summary}");
+        nop_p50 = Some(summary.p50);
+        summaries.push((labels, summary));
+        data.extend(samples);
+    }
+
+    // Latency and codec time against encoded size, per shape.
+    let mut latencies: HashMap<Shape, Vec<(f64, f64)>> = HashMap::new();
+    let mut codecs: HashMap<Shape, Vec<(f64, f64)>> = HashMap::new();
+    for &shape in &args.shapes {
+        for &exp in &args.exps {
+            let n_bytes = 1usize << exp;
+            let payload = shape.payload(n_bytes);
+            let encoded = payload.encoded_len();
+            // The argument and the result are each encoded and decoded once.
+            // A `DFut` never passes through the caller.
+            let codec = (shape != Shape::DFut).then(|| payload.codec_time(args.codec_tries) * 2);
+            let payload = (shape != Shape::DFut).then_some(payload);
+
+            let labels = Labels::new().with("shape", shape).with("bytes", encoded);
+            let samples = bench::run_with_setup(
+                &bench_cfg(&labels),
+                senders(),
+                move || payload.clone(),
+                move |client, payload| async move {
+                    // The expected and the returned length.
+                    let lens = match payload {
+                        None => {
+                            let f = client.nop(n_bytes as u64).await?;
+                            let f = client.len_of(f).await?;
+                            (n_bytes, client.d_await(f).await? as usize)
+                        }
+                        Some(Payload::Bytes(v)) => {
+                            let len = v.len();
+                            let f = client.echo_bytes(v).await?;
+                            (len, client.d_await(f).await?.len())
+                        }
+                        Some(Payload::U64s(v)) => {
+                            let len = v.len();
+                            let f = client.echo_u64s(v).await?;
+                            (len, client.d_await(f).await?.len())
+                        }
+                        Some(Payload::Records(v)) => {
+                            let len = v.len();
+                            let f = client.echo_records(v).await?;
+                            (len, client.d_await(f).await?.len())
+                        }
+                        Some(Payload::Strings(v)) => {
+                            let len = v.len();
+                            let f = client.echo_strings(v).await?;
+                            (len, client.d_await(f).await?.len())
+                        }
+                    };
+                    Ok::<_, dfut::Error>(lens)
+                },
+                |(want, got)| assert_eq!(got, want),
+            )
+            .await;
+
+            let summary = Summary::new(&samples);
+            println!("{labels} codec={codec:?}");
+            println!("{summary}");
+            latencies
+                .entry(shape)
+                .or_default()
+                .push((encoded as f64, summary.p50.as_secs_f64()));
+            if let Some(codec) = codec {
+                codecs
+                    .entry(shape)
+                    .or_default()
+                    .push((encoded as f64, codec.as_secs_f64()));
+            }
+            summaries.push((labels, summary));
+            data.extend(samples);
+        }
+    }
+
+    println!();
+    println!("costs");
+    for shape in &args.shapes {
+        println!("{shape}");
+        print_costs("p50", &latencies[shape]);
+        if let (Shape::DFut, Some(nop_p50)) = (shape, nop_p50) {
+            // What `len_of` and passing the bytes as a `DFut` add to one call.
+            let points: Vec<_> = latencies[shape]
+                .iter()
+                .map(|&(x, y)| (x, y - nop_p50.as_secs_f64()))
+                .collect();
+            print_costs("p50 over nop", &points);
+        }
+        if let Some(points) = codecs.get(shape) {
+            print_costs("codec", points);
+        }
+    }
+
+    bench::write_csv("payload-bench-data.csv", &data).unwrap();
+    bench::write_summary_csv("payload-bench.csv", &summaries).unwrap();
+    Results::new(started_at, &args, &topology, data)
+        .write("payload-bench-data.json")
+        .unwrap();
+}
@@ -0,0 +1,121 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::{Parser, Subcommand};
+
+use dfut_example::bench::{self, Sample};
+use dfut_example::plot::{self, Chart, Metric};
+
+/// Renders benchmark CSVs (e.g. `no-op-data-*.csv`, `sort-with-errors-data.csv`)
+/// as SVG charts.
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(subcommand)]
+    chart: ChartArgs,
+
+    /// Where to write the SVG, defaults to `<chart>.svg`.
+    #[arg(short, long, global = true)]
+    output: Option<PathBuf>,
+
+    #[arg(long, global = true, default_value_t = 1024)]
+    width: u32,
+
+    #[arg(long, global = true, default_value_t = 640)]
+    height: u32,
+}
+
+#[derive(Subcommand, Debug)]
+enum ChartArgs {
+    /// Latency of each group over the run.
+    LatencyOverTime {
+        #[arg(required = true)]
+        csvs: Vec<PathBuf>,
+
+        /// One line per value of these labels, defaults to all labels.
+        #[arg(long, value_delimiter = ',')]
+        keys: Option<Vec<String>>,
+
+        #[arg(long, default_value_t = 0.5)]
+        quantile: f64,
+
+        #[arg(long, default_value_t = 1.)]
+        bucket_secs: f64,
+    },
+    /// Latency against a numeric label, e.g. per `exp_id` against `size`.
+    Latency {
+        #[arg(required = true)]
+        csvs: Vec<PathBuf>,
+
+        #[arg(long, default_value = "size")]
+        x: String,
+
+        /// One line per value of these labels.
+        #[arg(long, value_delimiter = ',', default_value = "exp_id")]
+        series: Vec<String>,
+
+        #[arg(long, default_value_t = 0.5)]
+        quantile: f64,
+    },
+    /// Throughput against a numeric label, e.g. against `n_workers` across
+    /// the CSVs of several `no-op` runs.
+    Throughput {
+        #[arg(required = true)]
+        csvs: Vec<PathBuf>,
+
+        #[arg(long, default_value = "n_workers")]
+        x: String,
+
+        /// One line per value of these labels.
+        #[arg(long, value_delimiter = ',', default_value = "exp")]
+        series: Vec<String>,
+    },
+}
+
+fn load(csvs: &[PathBuf]) -> Vec<Sample> {
+    csvs.iter()
+        .flat_map(|path| {
+            bench::read_csv(path).unwrap_or_else(|e| panic!("failed to read {path:?}: {e}"))
+        })
+        .collect()
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let (name, chart): (&str, Chart) = match &args.chart {
+        ChartArgs::LatencyOverTime {
+            csvs,
+            keys,
+            quantile,
+            bucket_secs,
+        } => (
+            "latency-over-time",
+            plot::latency_over_time(
+                &load(csvs),
+                keys.as_deref(),
+                *quantile,
+                Duration::from_secs_f64(*bucket_secs),
+            ),
+        ),
+        ChartArgs::Latency {
+            csvs,
+            x,
+            series,
+            quantile,
+        } => (
+            "latency",
+            plot::by_label(&load(csvs), x, series, Metric::Latency(*quantile)).unwrap(),
+        ),
+        ChartArgs::Throughput { csvs, x, series } => (
+            "throughput",
+            plot::by_label(&load(csvs), x, series, Metric::Throughput).unwrap(),
+        ),
+    };
+
+    let svg = plot::render(&chart, (args.width, args.height)).unwrap();
+    let path = args
+        .output
+        .unwrap_or_else(|| PathBuf::from(format!("{name}.svg")));
+    std::fs::write(&path, svg).unwrap();
+    println!("wrote {path:?}");
+}
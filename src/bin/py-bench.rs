@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::Parser;
+use serde::Serialize;
+
+use dfut_example::bench::{self, BenchCfg, Labels, Stop, Summary};
+use dfut_example::patterns::py::{self, PyError, Script, Value, Worker, WorkerRootClient};
+use dfut_example::py_pool::PyPoolArgs;
+use dfut_example::results::Results;
+use dfut_example::topology::TopologyArgs;
+use dfut_example::{now, ready};
+
+const F_NAME: &str = "add";
+
+/// A small task in a script of realistic size, so that compiling it costs
+/// more than running it.
+fn script(n_helpers: usize) -> String {
+    let mut script = String::from("def add(**kwargs):\n    return kwargs['a'] + kwargs['b']\n");
+    for i in 0..n_helpers {
+        script.push_str(&format!(
+            "\ndef helper_{i}(xs):\n    return [x * {i} for x in xs if x % 2 == 0]\n"
+        ));
+    }
+    script
+}
+
+#[derive(Parser, Debug, Serialize)]
+struct Args {
+    #[command(flatten)]
+    topology: TopologyArgs,
+
+    #[command(flatten)]
+    py_pool: PyPoolArgs,
+
+    /// `uncached` compiles the script on every call (`run_py`), `cached`
+    /// registers it once and calls it by id (`call_py`).
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "uncached,cached",
+        value_parser = ["uncached", "cached"]
+    )]
+    modes: Vec<String>,
+
+    /// Helper functions padding the script.
+    #[arg(long, default_value_t = 200)]
+    n_helpers: usize,
+
+    /// Concurrent callers.
+    #[arg(long, default_value_t = 10)]
+    n_senders: usize,
+
+    #[arg(long, default_value_t = 10)]
+    duration_secs: u64,
+
+    /// Write the samples of all modes to this CSV.
+    #[arg(long)]
+    csv_path: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+    let started_at = now();
+
+    tracing_subscriber::fmt::init();
+
+    let topology = args.topology.load();
+    args.py_pool.install();
+
+    ready::start_in_process(&topology, Worker::serve_forever)
+        .await
+        .unwrap();
+
+    let root_client = WorkerRootClient::new(&topology.global_scheduler_address, "unique-id").await;
+    let client = root_client.new_client();
+
+    let script = script(args.n_helpers);
+    let kwargs: HashMap<String, Value> = [
+        ("a".to_string(), Value::Int(1)),
+        ("b".to_string(), Value::Int(2)),
+    ]
+    .into();
+
+    let verify = |v: Result<Value, PyError>| assert_eq!(v, Ok(Value::Int(3)));
+    let mut data = Vec::new();
+    for mode in &args.modes {
+        let cfg = BenchCfg {
+            stop: Stop::Duration(Duration::from_secs(args.duration_secs)),
+            labels: Labels::new()
+                .with("mode", mode)
+                .with("n_helpers", args.n_helpers),
+            log_every: 0,
+            ..Default::default()
+        };
+        let senders = (0..args.n_senders)
+            .map(|_| root_client.new_client())
+            .collect();
+
+        let samples = match mode.as_str() {
+            "uncached" => {
+                let (script, kwargs) = (script.clone(), kwargs.clone());
+                bench::run_with_setup(
+                    &cfg,
+                    senders,
+                    || (),
+                    move |client, ()| {
+                        let (script, kwargs) = (script.clone(), kwargs.clone());
+                        async move {
+                            let f = client.run_py(F_NAME.to_string(), script, kwargs).await?;
+                            client.d_await(f).await
+                        }
+                    },
+                    verify,
+                )
+                .await
+            }
+            "cached" => {
+                let script = Script::new(script.as_str());
+                let f = client
+                    .register_script(script.text().to_string())
+                    .await
+                    .unwrap();
+                assert_eq!(client.d_await(f).await.unwrap(), Ok(script.id()));
+
+                let kwargs = kwargs.clone();
+                bench::run_with_setup(
+                    &cfg,
+                    senders,
+                    || (),
+                    move |client, ()| {
+                        let (script, kwargs) = (script.clone(), kwargs.clone());
+                        async move { py::call_by_id(&client, &script, F_NAME, kwargs).await }
+                    },
+                    verify,
+                )
+                .await
+            }
+            _ => unreachable!(),
+        };
+
+        println!("mode={mode}");
+        println!("{}", Summary::new(&samples));
+        data.extend(samples);
+    }
+
+    if let Some(path) = &args.csv_path {
+        bench::write_csv(path, &data).unwrap();
+    }
+    Results::new(started_at, &args, &topology, data)
+        .write("py-bench-data.json")
+        .unwrap();
+}
@@ -1,16 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-use dfut::{
-    into_dfut, DFut, DResult, GlobalScheduler, GlobalSchedulerCfg, Runtime, WorkerServerConfig,
-};
-use pyo3::prelude::*;
-use pyo3::types::IntoPyDict;
-use serde::{Deserialize, Serialize};
+use clap::Parser;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-enum Error {
-    PyError,
-}
+use dfut_example::patterns::py::{Value, Worker, WorkerRootClient};
+use dfut_example::py_pool::PyPoolArgs;
+use dfut_example::ready;
+use dfut_example::topology::TopologyArgs;
 
 const F_NAME: &str = "do_work";
 
@@ -21,69 +16,65 @@ def do_work(**kwargs):
         print(f'hello world, my name is {name}')
     else:
         print('hello world')
-    return 42
+    return {'answer': 42, 'scores': [s * 2 for s in kwargs['scores']]}
+
+def fail(**kwargs):
+    return kwargs['n'] / 0
 "#;
 
-fn run_py(f_name: String, script: String, kwargs: HashMap<String, String>) -> PyResult<u64> {
-    Python::with_gil(|py| {
-        let fun: Py<PyAny> = PyModule::from_code_bound(py, &script, "", "")?
-            .getattr(f_name.as_str())?
-            .into();
-        let r = fun.call_bound(py, (), Some(&kwargs.into_py_dict_bound(py)))?;
-        r.extract(py)
-    })
-}
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(flatten)]
+    topology: TopologyArgs,
 
-#[derive(Debug, Clone)]
-pub struct Worker {
-    runtime: Runtime,
-}
-
-#[into_dfut]
-impl Worker {
-    pub async fn run_py(
-        &self,
-        f_name: String,
-        script: String,
-        kwargs: HashMap<String, String>,
-    ) -> DResult<Result<u64, Error>> {
-        Ok(run_py(f_name, script, kwargs).map_err(|_| Error::PyError))
-    }
+    #[command(flatten)]
+    py_pool: PyPoolArgs,
 }
 
 #[tokio::main]
 async fn main() {
+    let args = Args::parse();
+
     tracing_subscriber::fmt::init();
     let prometheus_handle = metrics_exporter_prometheus::PrometheusBuilder::new()
         .install_recorder()
         .unwrap();
 
-    let global_scheduler_address = "http://127.0.0.1:8120";
-
-    tokio::spawn(GlobalScheduler::serve_forever(GlobalSchedulerCfg {
-        address: global_scheduler_address.to_string(),
-        ..Default::default()
-    }));
+    let topology = args.topology.load();
+    args.py_pool.install();
 
-    (1..=9).for_each(|i| {
-        tokio::spawn(Worker::serve_forever(WorkerServerConfig {
-            local_server_address: format!("http://127.0.0.1:812{i}"),
-            global_scheduler_address: global_scheduler_address.to_string(),
-            ..Default::default()
-        }));
-    });
+    ready::start_in_process(&topology, Worker::serve_forever)
+        .await
+        .unwrap();
 
-    let root_client = WorkerRootClient::new(&global_scheduler_address, "unique-id").await;
+    let root_client = WorkerRootClient::new(&topology.global_scheduler_address, "unique-id").await;
     let client = root_client.new_client();
 
     let mut kwargs = HashMap::new();
-    kwargs.insert("name".to_string(), "bob".to_string());
+    kwargs.insert("name".to_string(), Value::from("bob"));
+    kwargs.insert("scores".to_string(), Value::from(vec![1.5, 2.5]));
     let fut = client
         .run_py(F_NAME.to_string(), SCRIPT.to_string(), kwargs)
         .await
         .unwrap();
     let result = client.d_await(fut).await.unwrap().unwrap();
-    assert_eq!(result, 42);
+    assert_eq!(
+        result,
+        Value::Dict(BTreeMap::from([
+            ("answer".to_string(), Value::Int(42)),
+            ("scores".to_string(), Value::from(vec![3., 5.])),
+        ]))
+    );
+
+    let mut kwargs = HashMap::new();
+    kwargs.insert("n".to_string(), Value::Int(1));
+    let fut = client
+        .run_py("fail".to_string(), SCRIPT.to_string(), kwargs)
+        .await
+        .unwrap();
+    let err = client.d_await(fut).await.unwrap().unwrap_err();
+    assert_eq!(err.type_name, "ZeroDivisionError");
+    println!("fail raised as expected:\n{err}");
 
     println!();
     println!("metrics");
@@ -1,103 +1,98 @@
-use dfut::{
-    d_await, d_box, d_cancel, into_dfut, DFut, DResult, GlobalScheduler, GlobalSchedulerCfg,
-    Runtime, WorkerServerConfig,
+use clap::Parser;
+
+use dfut_example::patterns::share::{
+    Dataset, HyperParams, Model, SearchCfg, Worker, WorkerRootClient,
 };
+use dfut_example::ready;
+use dfut_example::topology::TopologyArgs;
 
-#[derive(Debug, Clone)]
-pub struct Worker {
-    runtime: Runtime,
-}
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(flatten)]
+    topology: TopologyArgs,
 
-#[into_dfut]
-impl Worker {
-    // Supervisor. Inspired by:
-    // https://docs.ray.io/en/latest/ray-core/patterns/tree-of-actors.html.
-    pub async fn supervised_train(
-        &self,
-        hyperparams: Vec<f64>,
-        data: Vec<f64>,
-    ) -> DResult<Vec<Vec<f64>>> {
-        let data = d_box!(data);
-
-        let data_dfuts = self
-            .runtime
-            .share_n(&data, hyperparams.len() as u64)
-            .await?;
-
-        let mut v = Vec::new();
-        for (hyperparam, data) in hyperparams.into_iter().zip(data_dfuts) {
-            v.push(self.train_epoch(hyperparam, data).await?);
-        }
+    /// linear or logistic.
+    #[arg(long, default_value_t = Model::Logistic)]
+    model: Model,
 
-        let mut o = Vec::new();
-        for f in v {
-            o.push(d_await!(f));
-        }
+    #[arg(long, default_value_t = 100_000)]
+    n_samples: usize,
 
-        d_cancel!(data);
+    #[arg(long, default_value_t = 10)]
+    n_features: usize,
 
-        Ok(o)
-    }
+    /// Seeds the synthetic dataset.
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
 
-    pub async fn train_epoch(&self, hyperparam: f64, data: DFut<Vec<f64>>) -> DResult<Vec<f64>> {
-        let mut v = Vec::new();
-        for data in d_await!(data) {
-            v.push(self.train(hyperparam, data).await?);
-        }
+    /// The search tries every combination of learning rate and L2 penalty.
+    #[arg(long, value_delimiter = ',', default_value = "0.01,0.1,1,10,100")]
+    learning_rates: Vec<f64>,
 
-        let mut o = Vec::new();
-        for f in v {
-            o.push(d_await!(f));
-        }
+    #[arg(long, value_delimiter = ',', default_value = "0,0.01")]
+    l2s: Vec<f64>,
 
-        Ok(o)
-    }
+    #[arg(long, default_value_t = SearchCfg::default().epochs)]
+    epochs: u64,
 
-    pub async fn train(&self, hyperparam: f64, data: f64) -> DResult<f64> {
-        Ok(hyperparam * data)
-    }
+    /// Dataset shards, defaults to the number of workers.
+    #[arg(long)]
+    n_shards: Option<u64>,
+
+    #[arg(long, default_value_t = SearchCfg::default().stop_ratio)]
+    stop_ratio: f64,
 }
 
 #[tokio::main]
 async fn main() {
+    let args = Args::parse();
+
     tracing_subscriber::fmt::init();
     let prometheus_handle = metrics_exporter_prometheus::PrometheusBuilder::new()
         .install_recorder()
         .unwrap();
 
-    let global_scheduler_address = "http://127.0.0.1:8120";
-
-    tokio::spawn(GlobalScheduler::serve_forever(GlobalSchedulerCfg {
-        address: global_scheduler_address.to_string(),
-        ..Default::default()
-    }));
+    let topology = args.topology.load();
 
-    (1..=9).for_each(|i| {
-        tokio::spawn(Worker::serve_forever(WorkerServerConfig {
-            local_server_address: format!("http://127.0.0.1:812{i}"),
-            global_scheduler_address: global_scheduler_address.to_string(),
-            ..Default::default()
-        }));
-    });
+    ready::start_in_process(&topology, Worker::serve_forever)
+        .await
+        .unwrap();
 
-    let root_client = WorkerRootClient::new(&global_scheduler_address, "unique-id").await;
+    let root_client = WorkerRootClient::new(&topology.global_scheduler_address, "unique-id").await;
     let client = root_client.new_client();
 
-    let hyperparams = vec![1., 2., 3., 4.];
-    let data = vec![1., 2., 3.];
-    let fut = client.supervised_train(hyperparams, data).await.unwrap();
-    let model1 = client.d_await(fut).await.unwrap();
-    assert_eq!(
-        model1,
-        vec![
-            vec![1., 2., 3.],
-            vec![2., 4., 6.],
-            vec![3., 6., 9.],
-            vec![4., 8., 12.]
-        ]
-    );
+    let data = Dataset::synthetic(args.model, args.n_samples, args.n_features, args.seed);
+    let mut hyperparams = Vec::new();
+    for &learning_rate in &args.learning_rates {
+        for &l2 in &args.l2s {
+            hyperparams.push(HyperParams { learning_rate, l2 });
+        }
+    }
+    let cfg = SearchCfg {
+        epochs: args.epochs,
+        n_shards: args.n_shards.unwrap_or(topology.n_workers),
+        stop_ratio: args.stop_ratio,
+    };
+
+    let fut = client
+        .search(args.model, hyperparams, data, cfg)
+        .await
+        .unwrap();
+    let result = client.d_await(fut).await.unwrap();
 
-    tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+    for t in &result.trials {
+        println!(
+            "learning_rate={} l2={} epochs={} loss={:.6} stopped={}",
+            t.hyperparams.learning_rate, t.hyperparams.l2, t.epochs, t.loss, t.stopped
+        );
+    }
+    println!(
+        "best: learning_rate={} l2={} loss={:.6} weights={:?}",
+        result.best.hyperparams.learning_rate,
+        result.best.hyperparams.l2,
+        result.best.loss,
+        result.best.weights
+    );
 
     println!();
     println!("metrics");
@@ -1,240 +1,391 @@
-use std::time::{Duration, Instant};
+use std::convert::Infallible;
+use std::future::Future;
+use std::time::Duration;
 
-use dfut::{
-    d_await, into_dfut, DFut, DResult, GlobalScheduler, GlobalSchedulerCfg, Runtime,
-    WorkerServerConfig,
-};
+use clap::Parser;
 use rand::seq::SliceRandom;
-use rayon::prelude::*;
-use tokio_util::sync::CancellationToken;
-
-use dfut_example::now;
+use serde::Serialize;
 
-const BASE: u64 = 200_000;
-const N: u32 = 6;
+use dfut_example::bench::{self, BenchCfg, Labels, Sample, Stop};
+use dfut_example::fault::{self, FaultArgs, FaultCfg};
+use dfut_example::patterns::sort::{
+    self, local_quick_sort, Distribution, Pivot, QuickSortCfg, Worker, WorkerRootClient,
+};
+use dfut_example::results::Results;
+use dfut_example::topology::{Topology, TopologyArgs};
+use dfut_example::{now, ready};
 
 const P_FAIL: &[f64] = &[0., 0.01, 0.1];
 
-pub fn partition(mut v: Vec<u64>) -> (Vec<u64>, u64, Vec<u64>) {
-    let p = v.pop().unwrap();
-    let mut l = Vec::new();
-    let mut g = Vec::new();
-    for e in v {
-        if e > p {
-            g.push(e);
-        } else {
-            l.push(e);
-        }
-    }
-
-    (l, p, g)
+/// Checks that `v` is `0..size` without allocating the expected vector inside
+/// the timed region.
+fn assert_sorted(v: &[u64], size: u64) {
+    assert_eq!(v.len() as u64, size);
+    assert!(v.iter().enumerate().all(|(i, e)| i as u64 == *e));
 }
 
-pub fn local_quick_sort(mut v: Vec<u64>) -> Vec<u64> {
-    if v.len() < 200_000 {
-        v.sort();
-        return v;
-    }
-    let (l, p, g) = partition(v);
-    let l = local_quick_sort(l);
-    let g = local_quick_sort(g);
-    let mut out = Vec::new();
-    out.extend(l);
-    out.push(p);
-    out.extend(g);
-    out
+#[derive(Parser, Debug, Serialize)]
+struct Args {
+    #[command(flatten)]
+    topology: TopologyArgs,
+
+    // `p_fail` of `quick_sort` is set by each experiment, the other fault
+    // settings apply as configured.
+    #[command(flatten)]
+    faults: FaultArgs,
+
+    /// Compare std, quick, merge and sample sort across input distributions
+    /// instead of running the `p_fail` experiments.
+    #[arg(long)]
+    compare: bool,
+
+    /// Input distributions of `--compare`.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "uniform,sorted,reverse,zipf,duplicates"
+    )]
+    distributions: Vec<Distribution>,
+
+    /// Buckets of `sample_sort`, defaults to the number of workers.
+    #[arg(long)]
+    n_buckets: Option<u64>,
+
+    #[arg(long, default_value_t = 30)]
+    duration_secs: u64,
+
+    /// Inputs have `base_size * 2^i` elements for `i` in `1..=n_sizes`.
+    #[arg(long, default_value_t = 200_000)]
+    base_size: u64,
+
+    #[arg(long, default_value_t = 5)]
+    n_sizes: u32,
+
+    /// `quick_sort` cutoffs to sweep. `--compare` uses the first cutoff for
+    /// every algorithm, and the first `--blocking-partition` value.
+    #[arg(long, value_delimiter = ',', default_value = "200000")]
+    cutoffs: Vec<u64>,
+
+    /// `quick_sort` pivot of `--compare`. With `last`, sorted and reverse
+    /// sorted inputs never split and are sorted locally.
+    #[arg(long, default_value_t = Pivot::MedianOfThree)]
+    compare_pivot: Pivot,
+
+    /// `quick_sort` pivot strategies to sweep: last, median-of-three, random.
+    #[arg(long, value_delimiter = ',', default_value = "last")]
+    pivots: Vec<Pivot>,
+
+    /// Whether `quick_sort` partitions on the blocking pool, e.g. `true,false`.
+    #[arg(long, value_delimiter = ',', default_value = "true")]
+    blocking_partition: Vec<bool>,
 }
 
-#[derive(Debug, Clone)]
-pub struct Worker {
-    runtime: Runtime,
-}
+impl Args {
+    fn sizes(&self) -> Vec<u64> {
+        (1..=self.n_sizes)
+            .map(|i| self.base_size * 2u64.pow(i))
+            .collect()
+    }
 
-#[into_dfut]
-impl Worker {
-    pub async fn quick_sort(&self, p_fail: f64, mut v: Vec<u64>) -> DResult<Vec<u64>> {
-        if rand::random::<f64>() < p_fail {
-            return Err(dfut::Error::System);
-        }
-        if v.len() < 200_000 {
-            v.sort();
-            return Ok(v);
+    fn quick_sort_cfgs(&self) -> Vec<QuickSortCfg> {
+        let mut cfgs = Vec::new();
+        for &cutoff in &self.cutoffs {
+            for &pivot in &self.pivots {
+                for &blocking_partition in &self.blocking_partition {
+                    cfgs.push(QuickSortCfg {
+                        cutoff,
+                        pivot,
+                        blocking_partition,
+                    });
+                }
+            }
         }
+        cfgs
+    }
+}
 
-        let (l, p, g) = tokio::task::spawn_blocking(move || partition(v))
-            .await
-            .unwrap();
-
-        let l_fut = self.quick_sort(p_fail, l).await?;
-        let g_fut = self.quick_sort(p_fail, g).await?;
-
-        let mut out = Vec::new();
-        out.extend(d_await!(l_fut));
-        out.push(p);
-        out.extend(d_await!(g_fut));
+/// Runs a `quick_sort` experiment and prints how many of its partitions
+/// sorted their input instead of splitting, see `sort::sorted_splits`.
+async fn report_sorted_splits(
+    cfg: &BenchCfg,
+    run: impl Future<Output = Vec<Sample>>,
+) -> Vec<Sample> {
+    let before = sort::sorted_splits();
+    let samples = run.await;
+    println!(
+        "{} sorted_splits={} calls={}",
+        cfg.labels,
+        sort::sorted_splits() - before,
+        samples.len()
+    );
+    samples
+}
 
-        Ok(out)
+/// Labels every sample with the `quick_sort` parameters so that sweeps end up
+/// in one table; left empty for algorithms that do not take them.
+fn with_quick_sort_cfg(labels: Labels, cfg: Option<&QuickSortCfg>) -> Labels {
+    match cfg {
+        Some(cfg) => labels
+            .with("cutoff", cfg.cutoff)
+            .with("pivot", cfg.pivot)
+            .with("blocking_partition", cfg.blocking_partition),
+        None => labels
+            .with("cutoff", "")
+            .with("pivot", "")
+            .with("blocking_partition", ""),
     }
 }
 
 #[tokio::main]
 async fn main() {
+    let args = Args::parse();
+    let started_at = now();
+
     tracing_subscriber::fmt::init();
     let prometheus_handle = metrics_exporter_prometheus::PrometheusBuilder::new()
         .install_recorder()
         .unwrap();
 
-    let global_scheduler_address = "http://127.0.0.1:8220";
-
-    tokio::spawn(GlobalScheduler::serve_forever(GlobalSchedulerCfg {
-        address: global_scheduler_address.to_string(),
-        heart_beat_timeout: Duration::from_secs(20),
+    let topology = args.topology.load_with_defaults(Topology {
+        heart_beat_timeout_secs: Some(20),
         ..Default::default()
-    }));
-
-    let base_port_number = 8120;
-    (0..10).for_each(|i| {
-        tokio::spawn(Worker::serve_forever(WorkerServerConfig {
-            local_server_address: format!("http://127.0.0.1:{}", base_port_number + i),
-            global_scheduler_address: global_scheduler_address.to_string(),
-            ..Default::default()
-        }));
     });
 
-    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+    ready::start_in_process(&topology, Worker::serve_forever)
+        .await
+        .unwrap();
 
-    let root_client = WorkerRootClient::new(&global_scheduler_address, "unique-id").await;
+    let root_client = WorkerRootClient::new(&topology.global_scheduler_address, "unique-id").await;
+
+    let duration = Duration::from_secs(args.duration_secs);
+    let labels = Labels::new().with("n_workers", topology.n_workers);
+    let sizes = args.sizes();
+    let quick_sort_cfgs = args.quick_sort_cfgs();
+    if args.compare {
+        fault::install(args.faults.load());
+        let n_buckets = args.n_buckets.unwrap_or(topology.n_workers);
+        let data = compare(
+            &root_client,
+            &labels,
+            &sizes,
+            &args.distributions,
+            QuickSortCfg {
+                pivot: args.compare_pivot,
+                ..quick_sort_cfgs[0]
+            },
+            n_buckets,
+            duration,
+        )
+        .await;
+        bench::write_csv("sort-compare-data.csv", &data).unwrap();
+        Results::new(started_at, &args, &topology, data)
+            .write("sort-compare-data.json")
+            .unwrap();
+    } else {
+        let data = p_fail_experiments(
+            &root_client,
+            &labels,
+            &sizes,
+            &quick_sort_cfgs,
+            args.faults.load(),
+            duration,
+        )
+        .await;
+        bench::write_csv("sort-with-errors-data.csv", &data).unwrap();
+        Results::new(started_at, &args, &topology, data)
+            .write("sort-with-errors-data.json")
+            .unwrap();
+    }
 
-    let n_cpus = num_cpus::get();
+    println!("DONE");
 
-    let mut data = Vec::new();
-    for i in 1..N {
-        let size = BASE * 2u64.pow(i);
-
-        let exp_start = Instant::now();
-        let datum: Vec<Vec<_>> = (0..n_cpus)
-            .collect::<Vec<_>>()
-            .into_par_iter()
-            .map(|_| {
-                let mut data = Vec::new();
-                loop {
-                    let mut v: Vec<u64> = (0..size).collect();
-                    v.shuffle(&mut rand::thread_rng());
-
-                    let mut v_clone = v.clone();
-                    let start = Instant::now();
-                    v_clone.sort();
-                    let elapsed = start.elapsed();
-                    assert_eq!(v_clone, (0..size).collect::<Vec<_>>());
-                    data.push((
-                        now().as_millis().to_string(),
-                        size.to_string(),
-                        "std".to_string(),
-                        elapsed.as_secs_f64().to_string(),
-                    ));
-                    if exp_start.elapsed() >= Duration::from_secs(30) {
-                        break;
-                    }
-                }
-                data
-            })
-            .collect();
-        for d in datum {
-            data.extend(d);
-        }
+    // tokio::time::sleep(std::time::Duration::from_secs(10)).await;
 
-        let exp_start = Instant::now();
-        let datum: Vec<Vec<_>> = (0..n_cpus)
-            .collect::<Vec<_>>()
-            .into_par_iter()
-            .map(|_| {
-                let mut data = Vec::new();
-                loop {
-                    let mut v: Vec<u64> = (0..size).collect();
-                    v.shuffle(&mut rand::thread_rng());
-
-                    let v_clone = v.clone();
-                    let start = Instant::now();
-                    let got = local_quick_sort(v_clone);
-                    let elapsed = start.elapsed();
-                    assert_eq!(got, (0..size).collect::<Vec<_>>());
-                    data.push((
-                        now().as_millis().to_string(),
-                        size.to_string(),
-                        "local".to_string(),
-                        elapsed.as_secs_f64().to_string(),
-                    ));
-                    if exp_start.elapsed() >= Duration::from_secs(30) {
-                        break;
-                    }
-                }
-                data
-            })
-            .collect();
-        for d in datum {
-            data.extend(d);
-        }
+    if std::env::var("SHOW_METRICS").ok().is_some() {
+        println!();
+        println!("metrics");
+        println!("{}", prometheus_handle.render());
+    }
+}
 
-        for p_fail in P_FAIL {
-            let mut js = tokio::task::JoinSet::new();
-            let ct = CancellationToken::new();
-
-            for _ in 0..10 {
-                js.spawn({
-                    let ct = ct.clone();
-                    let exp_id = format!("p_fail={p_fail}");
-                    let client = root_client.new_client();
-
-                    async move {
-                        let mut data = Vec::new();
-                        loop {
-                            let mut v: Vec<u64> = (0..size).collect();
-                            v.shuffle(&mut rand::thread_rng());
-
-                            let v_clone = v.clone();
-                            let start = Instant::now();
-                            let f = client.quick_sort(*p_fail, v_clone).await.unwrap();
-                            let got = client.d_await(f).await.unwrap();
-                            let elapsed = start.elapsed();
-                            assert_eq!(got, (0..size).collect::<Vec<_>>());
-                            data.push((
-                                now().as_millis().to_string(),
-                                size.to_string(),
-                                exp_id.clone(),
-                                elapsed.as_secs_f64().to_string(),
-                            ));
-                            if ct.is_cancelled() {
-                                break;
-                            }
-                        }
-                        data
-                    }
-                });
-            }
+async fn p_fail_experiments(
+    root_client: &WorkerRootClient,
+    labels: &Labels,
+    sizes: &[u64],
+    quick_sort_cfgs: &[QuickSortCfg],
+    faults: FaultCfg,
+    duration: Duration,
+) -> Vec<Sample> {
+    let n_cpus = num_cpus::get();
 
-            tokio::time::sleep(Duration::from_secs(30)).await;
-            ct.cancel();
+    let shuffled = move |size: u64| {
+        move || {
+            let mut v: Vec<u64> = (0..size).collect();
+            v.shuffle(&mut rand::thread_rng());
+            v
+        }
+    };
 
-            while let Some(r) = js.join_next().await {
-                data.append(&mut r.unwrap());
+    let mut data = Vec::new();
+    for &size in sizes {
+        let cfg = |exp_id: &str, quick_sort_cfg: Option<&QuickSortCfg>| BenchCfg {
+            stop: Stop::Duration(duration),
+            labels: with_quick_sort_cfg(
+                labels.clone().with("size", size).with("exp_id", exp_id),
+                quick_sort_cfg,
+            ),
+            log_every: 0,
+            ..Default::default()
+        };
+
+        data.extend(
+            bench::run_with_setup(
+                &cfg("std", None),
+                vec![(); n_cpus],
+                shuffled(size),
+                move |_, mut v| async move {
+                    v.sort();
+                    Ok::<_, Infallible>(v)
+                },
+                move |got| assert_sorted(&got, size),
+            )
+            .await,
+        );
+
+        for quick_sort_cfg in quick_sort_cfgs {
+            let quick_sort_cfg = *quick_sort_cfg;
+            let local = cfg("local", Some(&quick_sort_cfg));
+            data.extend(
+                report_sorted_splits(
+                    &local,
+                    bench::run_with_setup(
+                        &local,
+                        vec![(); n_cpus],
+                        shuffled(size),
+                        move |_, v| async move {
+                            Ok::<_, Infallible>(local_quick_sort(v, &quick_sort_cfg))
+                        },
+                        move |got| assert_sorted(&got, size),
+                    ),
+                )
+                .await,
+            );
+
+            for p_fail in P_FAIL {
+                let mut faults = faults.clone();
+                faults
+                    .methods
+                    .entry("quick_sort".to_string())
+                    .or_default()
+                    .p_fail = *p_fail;
+                fault::install(faults);
+
+                let clients = (0..10).map(|_| root_client.new_client()).collect();
+                let distributed = cfg(&format!("p_fail={p_fail}"), Some(&quick_sort_cfg));
+                data.extend(
+                    report_sorted_splits(
+                        &distributed,
+                        bench::run_with_setup(
+                            &distributed,
+                            clients,
+                            shuffled(size),
+                            move |client, v| async move {
+                                let f = client.quick_sort(v, quick_sort_cfg).await?;
+                                client.d_await(f).await
+                            },
+                            move |got| assert_sorted(&got, size),
+                        ),
+                    )
+                    .await,
+                );
             }
         }
     }
 
-    let mut wtr = csv::Writer::from_path("sort-with-errors-data.csv").unwrap();
-    wtr.write_record(&["t", "size", "exp_id", "dur"]).unwrap();
-
-    for (now, size, exp_id, dur) in &data {
-        wtr.write_record(&[now, size, exp_id, dur]).unwrap();
-    }
+    data
+}
 
-    println!("DONE");
+/// Runs every algorithm on every distribution, checking the output against
+/// `sort_unstable` computed during setup.
+async fn compare(
+    root_client: &WorkerRootClient,
+    labels: &Labels,
+    sizes: &[u64],
+    distributions: &[Distribution],
+    quick_sort_cfg: QuickSortCfg,
+    n_buckets: u64,
+    duration: Duration,
+) -> Vec<Sample> {
+    let n_cpus = num_cpus::get();
 
-    // tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+    let input = |dist: Distribution, size: u64| {
+        move || {
+            let v = dist.generate(size);
+            let mut want = v.clone();
+            want.sort_unstable();
+            (v, want)
+        }
+    };
 
-    if std::env::var("SHOW_METRICS").ok().is_some() {
-        println!();
-        println!("metrics");
-        println!("{}", prometheus_handle.render());
+    let mut data = Vec::new();
+    for &size in sizes {
+        for &dist in distributions {
+            let cfg = |algo: &str| BenchCfg {
+                stop: Stop::Duration(duration),
+                labels: labels
+                    .clone()
+                    .with("size", size)
+                    .with("dist", dist)
+                    .with("algo", algo),
+                log_every: 0,
+                ..Default::default()
+            };
+
+            data.extend(
+                bench::run_with_setup(
+                    &cfg("std"),
+                    vec![(); n_cpus],
+                    input(dist, size),
+                    move |_, (mut v, want)| async move {
+                        v.sort();
+                        Ok::<_, Infallible>((v, want))
+                    },
+                    |(got, want)| assert_eq!(got, want),
+                )
+                .await,
+            );
+
+            for algo in ["quick_sort", "merge_sort", "sample_sort"] {
+                let clients = (0..10).map(|_| root_client.new_client()).collect();
+                let algo_cfg = cfg(algo);
+                data.extend(
+                    report_sorted_splits(
+                        &algo_cfg,
+                        bench::run_with_setup(
+                            &algo_cfg,
+                            clients,
+                            input(dist, size),
+                            move |client, (v, want)| async move {
+                                let f = match algo {
+                                    "quick_sort" => client.quick_sort(v, quick_sort_cfg).await?,
+                                    "merge_sort" => {
+                                        client.merge_sort(v, quick_sort_cfg.cutoff).await?
+                                    }
+                                    _ => {
+                                        client
+                                            .sample_sort(v, n_buckets, quick_sort_cfg.cutoff)
+                                            .await?
+                                    }
+                                };
+                                Ok::<_, dfut::Error>((client.d_await(f).await?, want))
+                            },
+                            |(got, want)| assert_eq!(got, want),
+                        ),
+                    )
+                    .await,
+                );
+            }
+        }
     }
+    data
 }
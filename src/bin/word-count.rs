@@ -0,0 +1,84 @@
+use std::path::PathBuf;
+use std::time::Instant;
+
+use clap::Parser;
+
+use dfut_example::patterns::word_count::{
+    local_word_count, read_shards, Combiner, Worker, WorkerRootClient,
+};
+use dfut_example::ready;
+use dfut_example::topology::TopologyArgs;
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(flatten)]
+    topology: TopologyArgs,
+
+    /// Directory of text files, one map shard per file.
+    #[arg(long)]
+    input_dir: PathBuf,
+
+    /// Reduce partitions, defaults to the number of workers.
+    #[arg(long)]
+    n_partitions: Option<u64>,
+
+    /// Map side combiner: none or sum.
+    #[arg(long, default_value_t = Combiner::Sum)]
+    combiner: Combiner,
+
+    /// Print this many of the most frequent words.
+    #[arg(long, default_value_t = 10)]
+    top: usize,
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+
+    tracing_subscriber::fmt::init();
+    let prometheus_handle = metrics_exporter_prometheus::PrometheusBuilder::new()
+        .install_recorder()
+        .unwrap();
+
+    let topology = args.topology.load();
+
+    let shards = read_shards(&args.input_dir).unwrap();
+    println!("read {} shards from {:?}", shards.len(), args.input_dir);
+
+    ready::start_in_process(&topology, Worker::serve_forever)
+        .await
+        .unwrap();
+
+    let root_client = WorkerRootClient::new(&topology.global_scheduler_address, "unique-id").await;
+    let client = root_client.new_client();
+
+    let start = Instant::now();
+    let want = local_word_count(&shards);
+    println!("local: took={:?}", start.elapsed());
+
+    let n_partitions = args.n_partitions.unwrap_or(topology.n_workers);
+    let start = Instant::now();
+    let f = client
+        .word_count(shards, n_partitions, args.combiner)
+        .await
+        .unwrap();
+    let got = client.d_await(f).await.unwrap();
+    println!(
+        "distributed: n_partitions={n_partitions} combiner={} took={:?}",
+        args.combiner,
+        start.elapsed()
+    );
+
+    assert_eq!(got, want);
+
+    let mut counts: Vec<(String, u64)> = got.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    println!("{} distinct words", counts.len());
+    for (word, count) in counts.iter().take(args.top) {
+        println!("{count:>10} {word}");
+    }
+
+    println!();
+    println!("metrics");
+    println!("{}", prometheus_handle.render());
+}
@@ -0,0 +1,159 @@
+use std::future::Future;
+use std::path::Path;
+use std::time::Duration;
+
+use dfut::WorkerServerConfig;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+
+use crate::bench::Sample;
+use crate::now;
+use crate::ready::Server;
+use crate::topology::Topology;
+
+#[derive(Debug, Clone)]
+pub struct ChaosCfg {
+    /// Time between two kills.
+    pub interval: Duration,
+    /// Time a killed worker stays down before it is restarted.
+    pub downtime: Duration,
+    /// Seeds the choice of workers to kill.
+    pub seed: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChaosEvent {
+    pub worker: String,
+    /// Wall clock time since the unix epoch, like [`Sample::t`].
+    pub killed_at: Duration,
+    /// Unset if the run ended while the worker was down.
+    pub restarted_at: Option<Duration>,
+}
+
+/// Every `cfg.interval`, stops a random worker, with the tasks it spawned, and
+/// restarts it on the same address after `cfg.downtime`, until `ct` is
+/// cancelled. `workers` are the servers returned by `ready::start_in_process`.
+/// Returns right away without workers.
+pub async fn run<F, Fut>(
+    cfg: ChaosCfg,
+    topology: Topology,
+    workers: Vec<Server>,
+    serve_forever: F,
+    ct: CancellationToken,
+) -> Vec<ChaosEvent>
+where
+    F: Fn(WorkerServerConfig) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    if workers.is_empty() {
+        return Vec::new();
+    }
+    let addresses = topology.worker_addresses();
+    // A killed worker is restarted before the next kill.
+    let mut workers: Vec<Option<Server>> = workers.into_iter().map(Some).collect();
+    let mut rng = StdRng::seed_from_u64(cfg.seed);
+    let mut events = Vec::new();
+    loop {
+        tokio::select! {
+            _ = ct.cancelled() => break,
+            _ = sleep(cfg.interval) => {}
+        }
+
+        let i = rng.gen_range(0..workers.len());
+        workers[i].take().unwrap().stop().await;
+        let mut event = ChaosEvent {
+            worker: addresses[i].clone(),
+            killed_at: now(),
+            restarted_at: None,
+        };
+        println!("chaos: killed {}", event.worker);
+
+        tokio::select! {
+            _ = ct.cancelled() => {
+                events.push(event);
+                break;
+            }
+            _ = sleep(cfg.downtime) => {}
+        }
+
+        workers[i] = Some(topology.spawn_worker(addresses[i].clone(), &serve_forever));
+        event.restarted_at = Some(now());
+        println!("chaos: restarted {}", event.worker);
+        events.push(event);
+    }
+    events
+}
+
+#[derive(Debug, Clone)]
+pub struct Recovery {
+    pub event: ChaosEvent,
+    /// Failed calls completed between this kill and the next one.
+    pub errors: u64,
+    /// From the kill to the first successful call after the last of those
+    /// errors. Zero if there were no errors, unset if no call succeeded.
+    pub recovered_after: Option<Duration>,
+}
+
+/// Attributes the samples of a run to the kill that preceded them.
+pub fn recoveries(events: &[ChaosEvent], samples: &[Sample]) -> Vec<Recovery> {
+    let mut samples: Vec<&Sample> = samples.iter().collect();
+    samples.sort_by_key(|s| s.t);
+
+    events
+        .iter()
+        .enumerate()
+        .map(|(i, event)| {
+            let end = events.get(i + 1).map_or(Duration::MAX, |e| e.killed_at);
+            let window: Vec<&Sample> = samples
+                .iter()
+                .copied()
+                .filter(|s| s.t >= event.killed_at && s.t < end)
+                .collect();
+
+            let errors = window.iter().filter(|s| !s.is_ok()).count() as u64;
+            let recovered_after = match window.iter().rev().find(|s| !s.is_ok()) {
+                None => Some(Duration::ZERO),
+                Some(last_error) => window
+                    .iter()
+                    .find(|s| s.is_ok() && s.t > last_error.t)
+                    .map(|s| s.t - event.killed_at),
+            };
+            Recovery {
+                event: event.clone(),
+                errors,
+                recovered_after,
+            }
+        })
+        .collect()
+}
+
+/// Writes `worker,killed_at,restarted_at,errors,recovered_after` with times
+/// in milliseconds since the unix epoch and `recovered_after` in seconds.
+pub fn write_csv(path: impl AsRef<Path>, recoveries: &[Recovery]) -> csv::Result<()> {
+    let mut wtr = csv::Writer::from_path(path)?;
+    wtr.write_record([
+        "worker",
+        "killed_at",
+        "restarted_at",
+        "errors",
+        "recovered_after",
+    ])?;
+    for r in recoveries {
+        wtr.write_record([
+            r.event.worker.clone(),
+            r.event.killed_at.as_millis().to_string(),
+            r.event
+                .restarted_at
+                .map(|t| t.as_millis().to_string())
+                .unwrap_or_default(),
+            r.errors.to_string(),
+            r.recovered_after
+                .map(|d| d.as_secs_f64().to_string())
+                .unwrap_or_default(),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
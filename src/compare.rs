@@ -0,0 +1,311 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::bench::{Labels, Sample};
+
+#[derive(Debug, Clone)]
+pub struct CompareCfg {
+    /// Align samples by these labels, all labels when unset.
+    pub keys: Option<Vec<String>>,
+    /// Latency quantiles to compare, e.g. 0.5 and 0.99.
+    pub quantiles: Vec<f64>,
+    pub n_resamples: usize,
+    /// Of the bootstrap intervals, e.g. 0.95.
+    pub confidence: f64,
+    /// Groups with more samples are randomly subsampled to this many before
+    /// bootstrapping, which sorts every resample.
+    pub max_samples: usize,
+    /// A quantile regressed when its relative change is above this with the
+    /// given confidence, e.g. 0.05 for 5%. An error or timeout rate regressed
+    /// when it rose by more than this.
+    pub threshold: f64,
+    pub seed: u64,
+}
+
+impl Default for CompareCfg {
+    fn default() -> Self {
+        Self {
+            keys: None,
+            quantiles: vec![0.5, 0.99],
+            n_resamples: 1_000,
+            confidence: 0.95,
+            max_samples: 10_000,
+            threshold: 0.05,
+            seed: 0,
+        }
+    }
+}
+
+/// The samples of one group of a run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Group {
+    /// Latencies of the successful samples.
+    pub ok: Vec<Duration>,
+    /// Failed samples, timeouts included.
+    pub errors: usize,
+    pub timeouts: usize,
+}
+
+impl Group {
+    pub fn len(&self) -> usize {
+        self.ok.len() + self.errors
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn error_rate(&self) -> f64 {
+        self.errors as f64 / self.len().max(1) as f64
+    }
+
+    pub fn timeout_rate(&self) -> f64 {
+        self.timeouts as f64 / self.len().max(1) as f64
+    }
+}
+
+/// Samples by their labels (or the `keys` of them).
+pub fn group(samples: &[Sample], keys: Option<&[String]>) -> BTreeMap<Labels, Group> {
+    let mut groups: BTreeMap<Labels, Group> = BTreeMap::new();
+    for s in samples {
+        let labels = match keys {
+            Some(keys) => s.labels.select(keys),
+            None => s.labels.clone(),
+        };
+        let group = groups.entry(labels).or_default();
+        if s.is_ok() {
+            group.ok.push(s.dur);
+        } else {
+            group.errors += 1;
+            group.timeouts += s.timed_out as usize;
+        }
+    }
+    groups
+}
+
+/// Nearest rank quantile of sorted, non-empty `v`.
+pub fn quantile(v: &[Duration], q: f64) -> Duration {
+    let rank = (q * v.len() as f64).ceil() as usize;
+    v[rank.clamp(1, v.len()) - 1]
+}
+
+fn relative_change(baseline: Duration, candidate: Duration) -> f64 {
+    candidate.as_secs_f64() / baseline.as_secs_f64().max(f64::MIN_POSITIVE) - 1.
+}
+
+fn resample_quantile(
+    rng: &mut StdRng,
+    v: &[Duration],
+    q: f64,
+    buf: &mut Vec<Duration>,
+) -> Duration {
+    buf.clear();
+    buf.extend((0..v.len()).map(|_| v[rng.gen_range(0..v.len())]));
+    buf.sort_unstable();
+    quantile(buf, q)
+}
+
+/// How one quantile changed between the baseline and the candidate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delta {
+    pub labels: Labels,
+    pub quantile: f64,
+    pub baseline: Duration,
+    pub candidate: Duration,
+    /// `candidate / baseline - 1`.
+    pub change: f64,
+    /// Bootstrap confidence interval of `change`.
+    pub ci: (f64, f64),
+    pub n_baseline: usize,
+    pub n_candidate: usize,
+    pub regression: bool,
+}
+
+impl fmt::Display for Delta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} p{} baseline={:?} candidate={:?} change={:+.1}% ci=[{:+.1}%, {:+.1}%] n={}/{}",
+            self.labels,
+            self.quantile * 100.,
+            self.baseline,
+            self.candidate,
+            self.change * 100.,
+            self.ci.0 * 100.,
+            self.ci.1 * 100.,
+            self.n_baseline,
+            self.n_candidate,
+        )?;
+        if self.regression {
+            write!(f, " REGRESSION")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares the `cfg.quantiles` of two latency samples, see [`compare`].
+pub fn compare_group(
+    cfg: &CompareCfg,
+    labels: &Labels,
+    baseline: &[Duration],
+    candidate: &[Duration],
+) -> Vec<Delta> {
+    let mut rng = StdRng::seed_from_u64(cfg.seed);
+    let mut subsample = |v: &[Duration]| {
+        let mut v = v.to_vec();
+        if v.len() > cfg.max_samples {
+            v.partial_shuffle(&mut rng, cfg.max_samples);
+            v.truncate(cfg.max_samples);
+        }
+        v.sort_unstable();
+        v
+    };
+    let baseline = subsample(baseline);
+    let candidate = subsample(candidate);
+
+    let mut buf = Vec::new();
+    cfg.quantiles
+        .iter()
+        .map(|&q| {
+            let mut changes: Vec<f64> = (0..cfg.n_resamples)
+                .map(|_| {
+                    let b = resample_quantile(&mut rng, &baseline, q, &mut buf);
+                    let c = resample_quantile(&mut rng, &candidate, q, &mut buf);
+                    relative_change(b, c)
+                })
+                .collect();
+            changes.sort_by(f64::total_cmp);
+            let at = |p: f64| {
+                let i = (p * (changes.len() - 1) as f64).round() as usize;
+                changes[i]
+            };
+            let alpha = (1. - cfg.confidence) / 2.;
+            let ci = (at(alpha), at(1. - alpha));
+
+            let (b, c) = (quantile(&baseline, q), quantile(&candidate, q));
+            Delta {
+                labels: labels.clone(),
+                quantile: q,
+                baseline: b,
+                candidate: c,
+                change: relative_change(b, c),
+                ci,
+                n_baseline: baseline.len(),
+                n_candidate: candidate.len(),
+                regression: ci.0 > cfg.threshold,
+            }
+        })
+        .collect()
+}
+
+/// How the share of failed samples in a group changed between the runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailureDelta {
+    pub labels: Labels,
+    /// Error rates of the baseline and the candidate, timeouts included.
+    pub errors: (f64, f64),
+    pub timeouts: (f64, f64),
+    pub n_baseline: usize,
+    pub n_candidate: usize,
+    pub regression: bool,
+}
+
+impl fmt::Display for FailureDelta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} errors={:.1}%->{:.1}% timeouts={:.1}%->{:.1}% n={}/{}",
+            self.labels,
+            self.errors.0 * 100.,
+            self.errors.1 * 100.,
+            self.timeouts.0 * 100.,
+            self.timeouts.1 * 100.,
+            self.n_baseline,
+            self.n_candidate,
+        )?;
+        if self.regression {
+            write!(f, " REGRESSION")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares the error and timeout rates of a group, `None` if neither run
+/// has failures. A rate that rose by more than `cfg.threshold`, e.g. from 1%
+/// to 7% with the default, is a regression.
+pub fn compare_failures(
+    cfg: &CompareCfg,
+    labels: &Labels,
+    baseline: &Group,
+    candidate: &Group,
+) -> Option<FailureDelta> {
+    if baseline.errors == 0 && candidate.errors == 0 {
+        return None;
+    }
+    let errors = (baseline.error_rate(), candidate.error_rate());
+    let timeouts = (baseline.timeout_rate(), candidate.timeout_rate());
+    let rose = |(b, c): (f64, f64)| c - b > cfg.threshold;
+    Some(FailureDelta {
+        labels: labels.clone(),
+        errors,
+        timeouts,
+        n_baseline: baseline.len(),
+        n_candidate: candidate.len(),
+        regression: rose(errors) || rose(timeouts),
+    })
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Comparison {
+    /// Latency changes of the groups with successful samples in both runs.
+    pub deltas: Vec<Delta>,
+    /// Groups with failed samples in either run.
+    pub failures: Vec<FailureDelta>,
+    /// Groups with samples in one run only. Groups missing from the
+    /// candidate are regressions.
+    pub only_baseline: Vec<Labels>,
+    pub only_candidate: Vec<Labels>,
+}
+
+impl Comparison {
+    /// Regressed latency quantiles and failure rates, and groups missing from
+    /// the candidate.
+    pub fn n_regressions(&self) -> usize {
+        self.deltas.iter().filter(|d| d.regression).count()
+            + self.failures.iter().filter(|d| d.regression).count()
+            + self.only_baseline.len()
+    }
+}
+
+/// Aligns the samples of two runs by their labels. In every group, it
+/// bootstraps the change of every quantile of the successful samples, which
+/// is a regression when the lower end of its confidence interval is above
+/// `cfg.threshold`, and compares the failure rates, see [`compare_failures`].
+pub fn compare(cfg: &CompareCfg, baseline: &[Sample], candidate: &[Sample]) -> Comparison {
+    assert!(cfg.n_resamples > 0, "n_resamples must be positive");
+    let baseline = group(baseline, cfg.keys.as_deref());
+    let mut candidate = group(candidate, cfg.keys.as_deref());
+
+    let mut comparison = Comparison::default();
+    for (labels, b) in baseline {
+        let Some(c) = candidate.remove(&labels) else {
+            comparison.only_baseline.push(labels);
+            continue;
+        };
+        if !b.ok.is_empty() && !c.ok.is_empty() {
+            comparison
+                .deltas
+                .extend(compare_group(cfg, &labels, &b.ok, &c.ok));
+        }
+        comparison
+            .failures
+            .extend(compare_failures(cfg, &labels, &b, &c));
+    }
+    comparison.only_candidate = candidate.into_keys().collect();
+    comparison
+}
@@ -0,0 +1,93 @@
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+use dfut::DResult;
+use serde::{Deserialize, Serialize};
+
+use crate::now;
+
+/// When a call is abandoned, as wall clock time since the unix epoch so it
+/// means the same on every worker it is passed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Deadline(pub Duration);
+
+impl Deadline {
+    pub fn after(timeout: Duration) -> Self {
+        Deadline(now() + timeout)
+    }
+
+    /// Zero once the deadline passed.
+    pub fn remaining(&self) -> Duration {
+        self.0.saturating_sub(now())
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+}
+
+/// How much longer than a call's own deadline the benchmark runner waits
+/// before dropping it.
+const BACKSTOP_GRACE: Duration = Duration::from_secs(1);
+
+/// `BenchCfg::timeout` for calls that get a `Deadline` after `timeout`. A
+/// runner timer armed at the same time as the deadline would fire first and
+/// drop the call before it could cancel what it started.
+pub fn backstop(timeout: Option<Duration>) -> Option<Duration> {
+    timeout.map(|timeout| timeout + BACKSTOP_GRACE)
+}
+
+/// A deadline passed before the call completed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Timeout;
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "deadline exceeded")
+    }
+}
+
+impl std::error::Error for Timeout {}
+
+/// Runs `f` until `deadline`, or to completion without one. `f` isn't started
+/// if the deadline already passed. `f` is dropped on timeout, but dropping a
+/// `DFut` doesn't cancel it: keep the ones `f` waits for outside of it and
+/// `d_cancel!` them, as `NoOpWorker::nop_fanout` does.
+pub async fn within<T>(
+    deadline: Option<Deadline>,
+    f: impl Future<Output = DResult<T>>,
+) -> DResult<Result<T, Timeout>> {
+    let Some(deadline) = deadline else {
+        return f.await.map(Ok);
+    };
+    if deadline.is_expired() {
+        return Ok(Err(Timeout));
+    }
+    match tokio::time::timeout(deadline.remaining(), f).await {
+        Ok(v) => v.map(Ok),
+        Err(_) => Ok(Err(Timeout)),
+    }
+}
+
+/// Why a driver's call failed, so timeouts and calls cancelled at shutdown
+/// can be told apart from other failures.
+#[derive(Debug, Clone)]
+pub enum CallError {
+    Timeout,
+    /// See `bench::InFlight`.
+    Cancelled,
+    Dfut(dfut::Error),
+}
+
+impl From<Timeout> for CallError {
+    fn from(_: Timeout) -> Self {
+        CallError::Timeout
+    }
+}
+
+impl From<dfut::Error> for CallError {
+    fn from(e: dfut::Error) -> Self {
+        CallError::Dfut(e)
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use dfut::DResult;
+use serde::{Deserialize, Serialize};
+
+/// How an injected failure surfaces.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FaultKind {
+    /// Return `dfut::Error::System`.
+    #[default]
+    Error,
+    /// Panic inside the worker method.
+    Panic,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MethodFaults {
+    /// Probability that a call fails.
+    pub p_fail: f64,
+    /// The first `fail_first_n` calls always fail.
+    pub fail_first_n: u64,
+    /// Added to every call before deciding whether it fails.
+    pub latency_ms: u64,
+    pub kind: FaultKind,
+}
+
+/// Fault injection config, keyed by worker method name (`"*"` matches every
+/// method without its own entry). Loaded from a TOML file such as:
+///
+/// ```toml
+/// seed = 42
+///
+/// [methods.quick_sort]
+/// p_fail = 0.01
+/// latency_ms = 5
+///
+/// [methods.retried_f]
+/// fail_first_n = 1
+/// kind = "panic"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FaultCfg {
+    pub seed: u64,
+    pub methods: HashMap<String, MethodFaults>,
+}
+
+#[derive(Debug)]
+pub enum FaultCfgError {
+    Io(std::io::Error),
+    Toml(toml::de::Error),
+}
+
+impl fmt::Display for FaultCfgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FaultCfgError::Io(e) => write!(f, "io: {e}"),
+            FaultCfgError::Toml(e) => write!(f, "toml: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for FaultCfgError {}
+
+impl FaultCfg {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, FaultCfgError> {
+        let s = std::fs::read_to_string(path).map_err(FaultCfgError::Io)?;
+        toml::from_str(&s).map_err(FaultCfgError::Toml)
+    }
+
+    pub fn with(mut self, method: &str, faults: MethodFaults) -> Self {
+        self.methods.insert(method.to_string(), faults);
+        self
+    }
+}
+
+struct Injector {
+    cfg: FaultCfg,
+    calls: HashMap<String, u64>,
+}
+
+// Worker structs only hold their `Runtime`, so the injector is process wide
+// (as are the workers of an in-process cluster).
+static INJECTOR: Mutex<Option<Injector>> = Mutex::new(None);
+
+/// Replaces the process wide fault config and resets all call counters.
+pub fn install(cfg: FaultCfg) {
+    *INJECTOR.lock().unwrap() = Some(Injector {
+        cfg,
+        calls: HashMap::new(),
+    });
+}
+
+/// Removes all injected faults.
+pub fn uninstall() {
+    *INJECTOR.lock().unwrap() = None;
+}
+
+// FNV-1a, stable across builds unlike `DefaultHasher`.
+pub(crate) fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+/// Uniform in `[0, 1)`, determined by the seed, the method and the index of
+/// the call, so the same sequence of calls fails the same way in every run.
+fn draw(seed: u64, method: &str, call: u64) -> f64 {
+    let x = splitmix64(seed ^ splitmix64(fnv1a(method) ^ splitmix64(call)));
+    (x >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Decides whether this call of `method` fails, without sleeping.
+fn decide(method: &str) -> Option<(Duration, Option<FaultKind>)> {
+    let mut injector = INJECTOR.lock().unwrap();
+    let injector = injector.as_mut()?;
+    let faults = injector
+        .cfg
+        .methods
+        .get(method)
+        .or_else(|| injector.cfg.methods.get("*"))?
+        .clone();
+
+    let calls = injector.calls.entry(method.to_string()).or_default();
+    let call = *calls;
+    *calls += 1;
+
+    let fail = call < faults.fail_first_n || draw(injector.cfg.seed, method, call) < faults.p_fail;
+    Some((
+        Duration::from_millis(faults.latency_ms),
+        fail.then_some(faults.kind),
+    ))
+}
+
+/// Call at the start of a worker method, e.g. `fault::inject("quick_sort").await?;`.
+pub async fn inject(method: &str) -> DResult<()> {
+    let Some((latency, fail)) = decide(method) else {
+        return Ok(());
+    };
+    if !latency.is_zero() {
+        tokio::time::sleep(latency).await;
+    }
+    match fail {
+        None => Ok(()),
+        Some(FaultKind::Error) => Err(dfut::Error::System),
+        Some(FaultKind::Panic) => panic!("injected fault in {method}"),
+    }
+}
+
+// Not a doc comment, clap would use it as the about text of every binary.
+#[derive(clap::Args, Debug, Clone, Default, Serialize)]
+pub struct FaultArgs {
+    /// Path to a TOML fault injection config.
+    #[arg(long)]
+    pub faults: Option<PathBuf>,
+
+    /// Overrides the seed of the fault config.
+    #[arg(long)]
+    pub fault_seed: Option<u64>,
+}
+
+impl FaultArgs {
+    pub fn load(&self) -> FaultCfg {
+        let mut cfg = match &self.faults {
+            Some(path) => FaultCfg::load(path)
+                .unwrap_or_else(|e| panic!("failed to load faults {path:?}: {e}")),
+            None => FaultCfg::default(),
+        };
+        if let Some(seed) = self.fault_seed {
+            cfg.seed = seed;
+        }
+        cfg
+    }
+}
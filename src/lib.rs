@@ -1,4 +1,22 @@
-use dfut::{d_await, into_dfut, DFut, DResult, Runtime};
+use std::collections::VecDeque;
+
+use dfut::{d_await, d_cancel, into_dfut, DFut, DResult, Runtime};
+
+use crate::deadline::{Deadline, Timeout};
+use crate::payload::Record;
+
+pub mod bench;
+pub mod chaos;
+pub mod compare;
+pub mod deadline;
+pub mod fault;
+pub mod patterns;
+pub mod payload;
+pub mod plot;
+pub mod py_pool;
+pub mod ready;
+pub mod results;
+pub mod topology;
 
 #[derive(Debug, Clone)]
 pub struct NoOpWorker {
@@ -7,22 +25,63 @@ pub struct NoOpWorker {
 
 #[into_dfut]
 impl NoOpWorker {
-    pub async fn nop_fanout(&self, n: u64, a: u64) -> DResult<()> {
-        let mut d_futs = Vec::new();
-        for _ in 0..n {
-            let tmp_d_fut = self.nop(a).await?;
-            d_futs.push(tmp_d_fut);
-        }
+    /// Gives up on the fan out at `deadline`, cancelling the `nop`s it didn't
+    /// start awaiting yet. The fan outs in `patterns` don't take deadlines.
+    pub async fn nop_fanout(
+        &self,
+        n: u64,
+        a: u64,
+        deadline: Option<Deadline>,
+    ) -> DResult<Result<(), Timeout>> {
+        // Kept outside of the deadline, since dropping a `DFut` doesn't cancel
+        // it. The one being awaited when the deadline passes is dropped with
+        // its `d_await!`.
+        let mut d_futs = VecDeque::new();
+        let result = deadline::within(deadline, async {
+            for _ in 0..n {
+                let tmp_d_fut = self.nop(a).await?;
+                d_futs.push_back(tmp_d_fut);
+            }
 
+            while let Some(d_fut) = d_futs.pop_front() {
+                d_await!(d_fut);
+            }
+            Ok(())
+        })
+        .await;
         for d_fut in d_futs {
-            d_await!(d_fut);
+            d_cancel!(d_fut);
         }
-        Ok(())
+        result
     }
 
     pub async fn nop(&self, a: u64) -> DResult<Vec<u8>> {
         Ok(vec![42u8; a as usize])
     }
+
+    // Return their argument, to measure what passing each shape costs, see
+    // `payload-bench`.
+
+    pub async fn echo_bytes(&self, v: Vec<u8>) -> DResult<Vec<u8>> {
+        Ok(v)
+    }
+
+    pub async fn echo_u64s(&self, v: Vec<u64>) -> DResult<Vec<u64>> {
+        Ok(v)
+    }
+
+    pub async fn echo_records(&self, v: Vec<Record>) -> DResult<Vec<Record>> {
+        Ok(v)
+    }
+
+    pub async fn echo_strings(&self, v: Vec<String>) -> DResult<Vec<String>> {
+        Ok(v)
+    }
+
+    /// Takes bytes another task produced, e.g. `nop`, by reference.
+    pub async fn len_of(&self, v: DFut<Vec<u8>>) -> DResult<u64> {
+        Ok(d_await!(v).len() as u64)
+    }
 }
 
 pub fn now() -> std::time::Duration {
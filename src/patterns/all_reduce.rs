@@ -0,0 +1,287 @@
+use std::fmt;
+use std::str::FromStr;
+
+use dfut::{d_await, d_cancel, into_dfut, DFut, DResult, Runtime};
+use serde::{Deserialize, Serialize};
+
+/// Element-wise reduction applied across participants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReduceOp {
+    Sum,
+    Min,
+    Max,
+    Mean,
+}
+
+impl ReduceOp {
+    pub const ALL: &'static [ReduceOp] =
+        &[ReduceOp::Sum, ReduceOp::Min, ReduceOp::Max, ReduceOp::Mean];
+
+    /// Folds `b` into `a`. `Mean` sums, see [`ReduceOp::finish`].
+    pub fn combine(&self, a: &mut [f64], b: &[f64]) {
+        assert_eq!(
+            a.len(),
+            b.len(),
+            "all-reduce inputs must have equal lengths"
+        );
+        for (a, b) in a.iter_mut().zip(b) {
+            *a = match self {
+                ReduceOp::Sum | ReduceOp::Mean => *a + b,
+                ReduceOp::Min => a.min(*b),
+                ReduceOp::Max => a.max(*b),
+            };
+        }
+    }
+
+    /// Applied once to the fully combined vector of `n` participants.
+    pub fn finish(&self, v: &mut [f64], n: usize) {
+        if *self == ReduceOp::Mean {
+            for e in v {
+                *e /= n as f64;
+            }
+        }
+    }
+
+    /// Reference result computed on a single thread. Panics without inputs,
+    /// which have no reduction.
+    pub fn local(&self, inputs: &[Vec<f64>]) -> Vec<f64> {
+        let mut out = inputs[0].clone();
+        for v in &inputs[1..] {
+            self.combine(&mut out, v);
+        }
+        self.finish(&mut out, inputs.len());
+        out
+    }
+}
+
+impl fmt::Display for ReduceOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ReduceOp::Sum => "sum",
+            ReduceOp::Min => "min",
+            ReduceOp::Max => "max",
+            ReduceOp::Mean => "mean",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for ReduceOp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ReduceOp::ALL
+            .iter()
+            .find(|op| op.to_string() == s)
+            .copied()
+            .ok_or_else(|| format!("unknown reduce op: {s}"))
+    }
+}
+
+/// Communication pattern of [`Worker::all_reduce`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Algorithm {
+    /// Pairwise reduction to a root, then the root is shared with everyone.
+    Tree,
+    /// Ring reduce-scatter of `n` chunks, then a ring all-gather.
+    Ring,
+}
+
+impl Algorithm {
+    pub const ALL: &'static [Algorithm] = &[Algorithm::Tree, Algorithm::Ring];
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Algorithm::Tree => "tree",
+            Algorithm::Ring => "ring",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Algorithm::ALL
+            .iter()
+            .find(|a| a.to_string() == s)
+            .copied()
+            .ok_or_else(|| format!("unknown all-reduce algorithm: {s}"))
+    }
+}
+
+/// Bounds of chunk `c` when splitting `len` elements into `n` chunks.
+fn chunk_range(len: usize, n: usize, c: usize) -> std::ops::Range<usize> {
+    (c * len / n)..((c + 1) * len / n)
+}
+
+#[derive(Debug, Clone)]
+pub struct Worker {
+    runtime: Runtime,
+}
+
+// Participants are logical: dfut decides which worker runs each task, the
+// data moves to whoever awaits a `DFut`.
+#[into_dfut]
+impl Worker {
+    /// Reduces the vectors of `inputs.len()` participants and returns the
+    /// result as received by each participant.
+    pub async fn all_reduce(
+        &self,
+        inputs: Vec<Vec<f64>>,
+        op: ReduceOp,
+        algorithm: Algorithm,
+    ) -> DResult<Vec<Vec<f64>>> {
+        let f = match algorithm {
+            Algorithm::Tree => self.tree_all_reduce(inputs, op).await?,
+            Algorithm::Ring => self.ring_all_reduce(inputs, op).await?,
+        };
+        Ok(d_await!(f))
+    }
+
+    pub async fn tree_all_reduce(
+        &self,
+        inputs: Vec<Vec<f64>>,
+        op: ReduceOp,
+    ) -> DResult<Vec<Vec<f64>>> {
+        if inputs.is_empty() {
+            return Ok(Vec::new());
+        }
+        let n = inputs.len();
+        let mut level = Vec::new();
+        for v in inputs {
+            level.push(self.contribute(v).await?);
+        }
+
+        while level.len() > 1 {
+            let mut next = Vec::new();
+            let mut it = level.into_iter();
+            while let Some(a) = it.next() {
+                match it.next() {
+                    Some(b) => next.push(self.combine(a, b, op).await?),
+                    None => next.push(a),
+                }
+            }
+            level = next;
+        }
+
+        let root = self.finish(level.pop().unwrap(), op, n as u64).await?;
+        let shared = self.runtime.share_n(&root, n as u64).await?;
+        d_cancel!(root);
+
+        let mut received = Vec::new();
+        for f in shared {
+            received.push(self.forward(f).await?);
+        }
+        let mut out = Vec::new();
+        for f in received {
+            out.push(d_await!(f));
+        }
+        Ok(out)
+    }
+
+    pub async fn ring_all_reduce(
+        &self,
+        inputs: Vec<Vec<f64>>,
+        op: ReduceOp,
+    ) -> DResult<Vec<Vec<f64>>> {
+        if inputs.is_empty() {
+            return Ok(Vec::new());
+        }
+        let n = inputs.len();
+        let len = inputs[0].len();
+
+        // chunks[i][c] is chunk `c` as currently held by participant `i`.
+        let mut chunks: Vec<Vec<Option<DFut<Vec<f64>>>>> = Vec::new();
+        for v in inputs {
+            let mut held = Vec::new();
+            for c in 0..n {
+                held.push(Some(
+                    self.contribute(v[chunk_range(len, n, c)].to_vec()).await?,
+                ));
+            }
+            chunks.push(held);
+        }
+
+        // Reduce-scatter: at step `s` participant `i` sends chunk `i - s` to
+        // `i + 1`, which folds it into its own. Afterwards participant `c - 1`
+        // holds the fully reduced chunk `c`.
+        for s in 0..n - 1 {
+            for i in 0..n {
+                let c = (i + n - s) % n;
+                let dst = (i + 1) % n;
+                let a = chunks[i][c].take().unwrap();
+                let b = chunks[dst][c].take().unwrap();
+                chunks[dst][c] = Some(self.combine(a, b, op).await?);
+            }
+        }
+
+        // All-gather: each reduced chunk travels once around the ring, every
+        // participant keeps a copy.
+        let mut gathered: Vec<Vec<Option<DFut<Vec<f64>>>>> =
+            (0..n).map(|_| (0..n).map(|_| None).collect()).collect();
+        for c in 0..n {
+            let mut holder = (c + n - 1) % n;
+            let reduced = chunks[holder][c].take().unwrap();
+            let mut f = self.finish(reduced, op, n as u64).await?;
+            for _ in 0..n - 1 {
+                let mut copies = self.runtime.share_n(&f, 2).await?;
+                d_cancel!(f);
+                let next = copies.pop().unwrap();
+                gathered[holder][c] = copies.pop();
+                holder = (holder + 1) % n;
+                f = self.forward(next).await?;
+            }
+            gathered[holder][c] = Some(f);
+        }
+
+        let mut received = Vec::new();
+        for held in gathered {
+            let held = held.into_iter().map(Option::unwrap).collect();
+            received.push(self.gather(held).await?);
+        }
+        let mut out = Vec::new();
+        for f in received {
+            out.push(d_await!(f));
+        }
+        Ok(out)
+    }
+
+    pub async fn contribute(&self, v: Vec<f64>) -> DResult<Vec<f64>> {
+        Ok(v)
+    }
+
+    pub async fn combine(
+        &self,
+        a: DFut<Vec<f64>>,
+        b: DFut<Vec<f64>>,
+        op: ReduceOp,
+    ) -> DResult<Vec<f64>> {
+        let mut a = d_await!(a);
+        let b = d_await!(b);
+        op.combine(&mut a, &b);
+        Ok(a)
+    }
+
+    pub async fn finish(&self, v: DFut<Vec<f64>>, op: ReduceOp, n: u64) -> DResult<Vec<f64>> {
+        let mut v = d_await!(v);
+        op.finish(&mut v, n as usize);
+        Ok(v)
+    }
+
+    /// Copies a vector to the participant running this task.
+    pub async fn forward(&self, v: DFut<Vec<f64>>) -> DResult<Vec<f64>> {
+        Ok(d_await!(v))
+    }
+
+    pub async fn gather(&self, chunks: Vec<DFut<Vec<f64>>>) -> DResult<Vec<f64>> {
+        let mut out = Vec::new();
+        for chunk in chunks {
+            out.extend(d_await!(chunk));
+        }
+        Ok(out)
+    }
+}
@@ -0,0 +1,71 @@
+use dfut::{d_await, into_dfut, DFut, DResult, Runtime};
+
+use crate::fault;
+use crate::patterns::sort::{partition_or_sort, QuickSortCfg, Split};
+
+#[derive(Debug, Clone)]
+pub struct Worker {
+    runtime: Runtime,
+}
+
+#[into_dfut]
+impl Worker {
+    // Foo Bar.
+    pub async fn foo(&self, a: Vec<String>) -> DResult<String> {
+        Ok(a.join(" ").to_string())
+    }
+
+    pub async fn bar(&self, a: usize, b: DFut<String>) -> DResult<String> {
+        let b = d_await!(b);
+        Ok((b + " ").repeat(a).trim().to_string())
+    }
+
+    pub async fn foo_bar(&self, a: usize) -> DResult<String> {
+        let v = vec!["hello".to_string(), "world".to_string()];
+        let sf = self.foo(v).await?;
+        let b: DFut<String> = self.bar(a, sf).await?;
+        Ok(d_await!(b))
+    }
+
+    // Sort. Inspired by: https://docs.ray.io/en/latest/ray-core/patterns/nested-tasks.html.
+    pub async fn quick_sort(&self, mut v: Vec<u64>, cfg: QuickSortCfg) -> DResult<Vec<u64>> {
+        if (v.len() as u64) < cfg.cutoff.max(1) {
+            v.sort();
+            return Ok(v);
+        }
+        let split = if cfg.blocking_partition {
+            tokio::task::spawn_blocking(move || partition_or_sort(v, cfg.pivot))
+                .await
+                .unwrap()
+        } else {
+            partition_or_sort(v, cfg.pivot)
+        };
+        let (l, p, g) = match split {
+            Split::Partitioned(l, p, g) => (l, p, g),
+            Split::Sorted(v) => return Ok(v),
+        };
+        let l_fut = self.quick_sort(l, cfg).await?;
+        let g_fut = self.quick_sort(g, cfg).await?;
+        let l = d_await!(l_fut);
+        let g = d_await!(g_fut);
+        let mut out = Vec::new();
+        out.extend(l);
+        out.push(p);
+        out.extend(g);
+        Ok(out)
+    }
+
+    pub async fn reconstruction(&self, v: u64) -> DResult<u64> {
+        // Since we don't retry from the driver and we don't retry on the
+        // current worker, we have the parent retry. We need one level of
+        // indirection.
+        let v = d_await!(self.retried_f(v).await?);
+        Ok(v)
+    }
+
+    // Fails as configured with `fault::install`, e.g. `fail_first_n = 1`.
+    pub async fn retried_f(&self, v: u64) -> DResult<u64> {
+        fault::inject("retried_f").await?;
+        Ok(2 * v)
+    }
+}
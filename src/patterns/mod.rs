@@ -0,0 +1,6 @@
+pub mod all_reduce;
+pub mod basic;
+pub mod py;
+pub mod share;
+pub mod sort;
+pub mod word_count;
@@ -0,0 +1,775 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use dfut::{d_await, d_cancel, into_dfut, DFut, DResult, Runtime};
+use pyo3::exceptions::{PyException, PyRuntimeError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::sync::GILOnceCell;
+use pyo3::types::{PyBool, PyDict, PyFloat, PyInt, PyList, PyString, PyTuple};
+use serde::{Deserialize, Serialize};
+
+use crate::fault::fnv1a;
+use crate::py_pool;
+
+/// Python values that can cross the wire as arguments and results.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Value {
+    None,
+    Bool(bool),
+    Int(i64),
+    #[serde(with = "float")]
+    Float(f64),
+    Str(String),
+    /// Python lists and tuples.
+    List(Vec<Value>),
+    /// Python dicts with `str` keys.
+    Dict(BTreeMap<String, Value>),
+}
+
+// JSON has no NaN or infinities, so human readable formats, i.e. the JSON of
+// interpreter subprocesses, carry them as the strings "nan", "inf" and "-inf".
+mod float {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Readable {
+        Finite(f64),
+        NonFinite(String),
+    }
+
+    pub fn serialize<S: Serializer>(v: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() || v.is_finite() {
+            return serializer.serialize_f64(*v);
+        }
+        serializer.serialize_str(if v.is_nan() {
+            "nan"
+        } else if *v > 0. {
+            "inf"
+        } else {
+            "-inf"
+        })
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        if !deserializer.is_human_readable() {
+            return f64::deserialize(deserializer);
+        }
+        match Readable::deserialize(deserializer)? {
+            Readable::Finite(v) => Ok(v),
+            Readable::NonFinite(s) => match s.as_str() {
+                "nan" => Ok(f64::NAN),
+                "inf" => Ok(f64::INFINITY),
+                "-inf" => Ok(f64::NEG_INFINITY),
+                _ => Err(serde::de::Error::custom(format!("not a float: {s:?}"))),
+            },
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(v: bool) -> Self {
+        Value::Bool(v)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(v: i64) -> Self {
+        Value::Int(v)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Value::Float(v)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Value::Str(v.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Value::Str(v)
+    }
+}
+
+impl<V: Into<Value>> From<Vec<V>> for Value {
+    fn from(v: Vec<V>) -> Self {
+        Value::List(v.into_iter().map(Into::into).collect())
+    }
+}
+
+impl ToPyObject for Value {
+    fn to_object(&self, py: Python<'_>) -> PyObject {
+        match self {
+            Value::None => py.None(),
+            Value::Bool(v) => v.to_object(py),
+            Value::Int(v) => v.to_object(py),
+            Value::Float(v) => v.to_object(py),
+            Value::Str(v) => v.to_object(py),
+            Value::List(v) => PyList::new_bound(py, v.iter().map(|v| v.to_object(py))).into(),
+            Value::Dict(v) => {
+                let dict = PyDict::new_bound(py);
+                for (k, v) in v {
+                    dict.set_item(k, v.to_object(py)).unwrap();
+                }
+                dict.into()
+            }
+        }
+    }
+}
+
+impl<'py> FromPyObject<'py> for Value {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        if ob.is_none() {
+            Ok(Value::None)
+        // `bool` is a subclass of `int`, so it goes first.
+        } else if ob.is_instance_of::<PyBool>() {
+            Ok(Value::Bool(ob.extract()?))
+        } else if ob.is_instance_of::<PyInt>() {
+            Ok(Value::Int(ob.extract()?))
+        } else if ob.is_instance_of::<PyFloat>() {
+            Ok(Value::Float(ob.extract()?))
+        } else if ob.is_instance_of::<PyString>() {
+            Ok(Value::Str(ob.extract()?))
+        } else if ob.is_instance_of::<PyList>() || ob.is_instance_of::<PyTuple>() {
+            Ok(Value::List(
+                ob.iter()?.map(|v| v?.extract()).collect::<PyResult<_>>()?,
+            ))
+        } else if let Ok(dict) = ob.downcast::<PyDict>() {
+            Ok(Value::Dict(
+                dict.iter()
+                    .map(|(k, v)| Ok((k.extract()?, v.extract()?)))
+                    .collect::<PyResult<_>>()?,
+            ))
+        } else {
+            Err(PyTypeError::new_err(format!(
+                "unsupported type: {}",
+                ob.get_type().name()?
+            )))
+        }
+    }
+}
+
+/// A Python exception raised while loading or running a script.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PyError {
+    /// E.g. `ZeroDivisionError`.
+    pub type_name: String,
+    pub message: String,
+    /// Formatted like Python prints it, unset if the exception has none.
+    pub traceback: Option<String>,
+}
+
+impl PyError {
+    pub fn new(py: Python<'_>, err: &PyErr) -> Self {
+        Self {
+            type_name: err
+                .get_type_bound(py)
+                .name()
+                .map(|n| n.into_owned())
+                .unwrap_or_else(|_| "<unknown>".to_string()),
+            message: err.value_bound(py).to_string(),
+            traceback: err.traceback_bound(py).and_then(|tb| tb.format().ok()),
+        }
+    }
+}
+
+impl PyError {
+    const UNKNOWN_SCRIPT: &'static str = "UnknownScript";
+
+    /// `call_py` of a script the worker process hasn't seen, or has evicted.
+    pub fn unknown_script(id: ScriptId) -> Self {
+        Self {
+            type_name: Self::UNKNOWN_SCRIPT.to_string(),
+            message: format!("script {id} is unknown to this worker process"),
+            traceback: None,
+        }
+    }
+
+    pub fn is_unknown_script(&self) -> bool {
+        self.type_name == Self::UNKNOWN_SCRIPT && self.traceback.is_none()
+    }
+}
+
+impl fmt::Display for PyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(traceback) = &self.traceback {
+            write!(f, "{traceback}")?;
+        }
+        write!(f, "{}: {}", self.type_name, self.message)
+    }
+}
+
+impl std::error::Error for PyError {}
+
+fn call(
+    py: Python<'_>,
+    module: &Bound<'_, PyModule>,
+    f_name: &str,
+    kwargs: &HashMap<String, Value>,
+) -> PyResult<Value> {
+    let py_kwargs = PyDict::new_bound(py);
+    for (k, v) in kwargs {
+        py_kwargs.set_item(k, v.to_object(py))?;
+    }
+    module
+        .getattr(f_name)?
+        .call((), Some(&py_kwargs))?
+        .extract()
+}
+
+/// Compiles `script` and calls `f_name(**kwargs)` from it.
+pub fn run_py(
+    f_name: &str,
+    script: &str,
+    kwargs: &HashMap<String, Value>,
+) -> Result<Value, PyError> {
+    Python::with_gil(|py| {
+        add_dfut_module(py)
+            .and_then(|_| PyModule::from_code_bound(py, script, "<script>", ""))
+            .and_then(|module| call(py, &module, f_name, kwargs))
+            .map_err(|e| PyError::new(py, &e))
+    })
+}
+
+/// Identifies a script by the hash of its text, so a driver can compute it
+/// without asking a worker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ScriptId(pub u64);
+
+impl ScriptId {
+    pub fn of(script: &str) -> Self {
+        ScriptId(fnv1a(script))
+    }
+}
+
+impl fmt::Display for ScriptId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// A script with its id, see [`call_by_id`]. The id is always the hash of the
+/// text, so a script can't be cached under another script's id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Script {
+    id: ScriptId,
+    text: String,
+}
+
+impl Script {
+    pub fn new(text: impl Into<String>) -> Self {
+        let text = text.into();
+        Self {
+            id: ScriptId::of(&text),
+            text,
+        }
+    }
+
+    pub fn id(&self) -> ScriptId {
+        self.id
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+/// How many scripts a worker process keeps compiled, and the text of. The
+/// oldest are evicted first; `call_py` fails for a script whose text was
+/// evicted, and the caller sends it again.
+pub const MAX_SCRIPTS: usize = 256;
+
+// Worker structs only hold their `Runtime`, so compiled modules are cached
+// per process, by `ScriptId`: every worker of an in-process cluster shares
+// them. Guarded by the GIL rather than a mutex, since running a script's top
+// level code can hand the GIL to a thread that would then wait for the mutex.
+static MODULES: GILOnceCell<Py<PyDict>> = GILOnceCell::new();
+
+// The text of the last `MAX_SCRIPTS` scripts this process has seen, so they
+// can be called by id. Only locked without the GIL.
+static SCRIPTS: Mutex<Option<Scripts>> = Mutex::new(None);
+
+#[derive(Default)]
+struct Scripts {
+    texts: HashMap<ScriptId, String>,
+    /// Oldest first.
+    order: VecDeque<ScriptId>,
+}
+
+fn remember(script: &Script) {
+    let mut scripts = SCRIPTS.lock().unwrap();
+    let scripts = scripts.get_or_insert_with(Scripts::default);
+    if scripts.texts.contains_key(&script.id) {
+        return;
+    }
+    if scripts.order.len() >= MAX_SCRIPTS {
+        let oldest = scripts.order.pop_front().unwrap();
+        scripts.texts.remove(&oldest);
+    }
+    scripts.texts.insert(script.id, script.text.clone());
+    scripts.order.push_back(script.id);
+}
+
+/// A script this process has seen, see `register_script` and `call_py`.
+pub(crate) fn known_script(id: ScriptId) -> Option<Script> {
+    let scripts = SCRIPTS.lock().unwrap();
+    let text = scripts.as_ref()?.texts.get(&id)?;
+    Some(Script {
+        id,
+        text: text.clone(),
+    })
+}
+
+/// The cached module of `script`, compiled first if it isn't cached yet.
+fn module<'py>(py: Python<'py>, script: &Script) -> PyResult<Bound<'py, PyModule>> {
+    let modules = MODULES
+        .get_or_init(py, || PyDict::new_bound(py).unbind())
+        .bind(py);
+    if let Some(module) = modules.get_item(script.id.0)? {
+        return Ok(module.downcast_into()?);
+    }
+    add_dfut_module(py)?;
+    let file_name = format!("<script {}>", script.id);
+    let module = PyModule::from_code_bound(py, &script.text, &file_name, "")?;
+    // Other threads may have compiled the script while its top level code ran,
+    // the first module wins.
+    match modules.get_item(script.id.0)? {
+        Some(module) => Ok(module.downcast_into()?),
+        None => {
+            // Dicts keep insertion order, so the first key is the oldest.
+            if modules.len() >= MAX_SCRIPTS {
+                if let Some((oldest, _)) = modules.iter().next() {
+                    modules.del_item(oldest)?;
+                }
+            }
+            modules.set_item(script.id.0, &module)?;
+            Ok(module)
+        }
+    }
+}
+
+/// Compiles `script` unless a module with the same id is already cached.
+pub fn register_script(script: &str) -> Result<ScriptId, PyError> {
+    let script = Script::new(script);
+    Python::with_gil(|py| {
+        module(py, &script)
+            .map(|_| script.id)
+            .map_err(|e| PyError::new(py, &e))
+    })
+}
+
+/// Calls `f_name(**kwargs)` from the cached module of `script`, compiling it
+/// first if it isn't cached.
+pub fn call_py(
+    script: &Script,
+    f_name: &str,
+    kwargs: &HashMap<String, Value>,
+) -> Result<Value, PyError> {
+    Python::with_gil(|py| {
+        module(py, script)
+            .and_then(|module| call(py, &module, f_name, kwargs))
+            .map_err(|e| PyError::new(py, &e))
+    })
+}
+
+/// A distributed task started from Python with the `dfut` module.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) enum Task {
+    RunPy {
+        f_name: String,
+        script: String,
+        kwargs: HashMap<String, Value>,
+    },
+    RegisterScript {
+        script: String,
+    },
+    CallPy {
+        script_id: ScriptId,
+        f_name: String,
+        kwargs: HashMap<String, Value>,
+    },
+}
+
+enum Pending {
+    Value(DFut<Result<Value, PyError>>),
+    ScriptId(DFut<Result<ScriptId, PyError>>),
+    /// Sent again with the text if the worker running it doesn't know the
+    /// script.
+    CallPy {
+        f: DFut<Result<Value, PyError>>,
+        script: Script,
+        f_name: String,
+        kwargs: HashMap<String, Value>,
+    },
+}
+
+// Counts the tasks cancelled because the Python task that started them
+// returned without awaiting or cancelling them.
+static ABANDONED: AtomicU64 = AtomicU64::new(0);
+
+/// Tasks that Python tasks of this process started but didn't await or
+/// cancel, and that were cancelled with `d_cancel!` when they returned.
+pub fn abandoned_tasks() -> u64 {
+    ABANDONED.load(Ordering::Relaxed)
+}
+
+fn cancel(pending: Pending) {
+    match pending {
+        Pending::Value(f) | Pending::CallPy { f, .. } => d_cancel!(f),
+        Pending::ScriptId(f) => d_cancel!(f),
+    }
+}
+
+/// The tasks started by one Python task, by handle. Only that task can await
+/// them, the ones it doesn't await are cancelled when it returns (when its
+/// `Tasks` is dropped).
+pub(crate) struct Tasks {
+    worker: Worker,
+    runtime: tokio::runtime::Handle,
+    pending: HashMap<u64, Pending>,
+    next_handle: u64,
+}
+
+impl Tasks {
+    pub(crate) fn new(worker: Worker) -> Self {
+        Self {
+            worker,
+            runtime: tokio::runtime::Handle::current(),
+            pending: HashMap::new(),
+            next_handle: 0,
+        }
+    }
+
+    /// Blocks, so it must run off the runtime's threads.
+    pub(crate) fn submit(&mut self, task: Task) -> Result<u64, String> {
+        // Python passes script ids, the worker that runs the call may need the
+        // text too.
+        let script = match &task {
+            Task::RegisterScript { script } => {
+                let script = Script::new(script.as_str());
+                remember(&script);
+                None
+            }
+            Task::CallPy { script_id, .. } => Some(
+                known_script(*script_id)
+                    .ok_or_else(|| format!("script {script_id} is unknown to this worker"))?,
+            ),
+            Task::RunPy { .. } => None,
+        };
+        let worker = &self.worker;
+        let pending = self
+            .runtime
+            .block_on(async move {
+                Ok::<_, dfut::Error>(match task {
+                    Task::RunPy {
+                        f_name,
+                        script,
+                        kwargs,
+                    } => Pending::Value(worker.run_py(f_name, script, kwargs).await?),
+                    Task::RegisterScript { script } => {
+                        Pending::ScriptId(worker.register_script(script).await?)
+                    }
+                    Task::CallPy {
+                        script_id,
+                        f_name,
+                        kwargs,
+                    } => Pending::CallPy {
+                        f: worker
+                            .call_py(script_id, f_name.clone(), kwargs.clone())
+                            .await?,
+                        script: script.unwrap(),
+                        f_name,
+                        kwargs,
+                    },
+                })
+            })
+            .map_err(|e| format!("failed to submit task: {e:?}"))?;
+        let handle = self.next_handle;
+        self.next_handle += 1;
+        self.pending.insert(handle, pending);
+        Ok(handle)
+    }
+
+    /// Script ids come back as their hex string, Python ints can't be `Value`s
+    /// past `i64::MAX`.
+    pub(crate) fn d_await(&mut self, handle: u64) -> Result<Result<Value, PyError>, String> {
+        let pending = self.take(handle)?;
+        let worker = &self.worker;
+        self.runtime
+            .block_on(async move {
+                Ok::<_, dfut::Error>(match pending {
+                    Pending::Value(f) => d_await!(f),
+                    Pending::ScriptId(f) => d_await!(f).map(|id| Value::Str(id.to_string())),
+                    Pending::CallPy {
+                        f,
+                        script,
+                        f_name,
+                        kwargs,
+                    } => match d_await!(f) {
+                        Err(e) if e.is_unknown_script() => {
+                            let f = worker.load_and_call_py(script.text, f_name, kwargs).await?;
+                            d_await!(f)
+                        }
+                        result => result,
+                    },
+                })
+            })
+            .map_err(|e| format!("failed to await task: {e:?}"))
+    }
+
+    pub(crate) fn d_cancel(&mut self, handle: u64) -> Result<(), String> {
+        cancel(self.take(handle)?);
+        Ok(())
+    }
+
+    pub(crate) fn block_on<F: std::future::Future>(&self, f: F) -> F::Output {
+        self.runtime.block_on(f)
+    }
+
+    fn take(&mut self, handle: u64) -> Result<Pending, String> {
+        self.pending
+            .remove(&handle)
+            .ok_or_else(|| format!("unknown or already awaited handle: {handle}"))
+    }
+}
+
+impl Drop for Tasks {
+    fn drop(&mut self) {
+        for (_, pending) in self.pending.drain() {
+            cancel(pending);
+            ABANDONED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+fn parse_script_id(s: &str) -> Result<ScriptId, String> {
+    u64::from_str_radix(s, 16)
+        .map(ScriptId)
+        .map_err(|_| format!("invalid script id: {s}"))
+}
+
+pyo3::create_exception!(dfut, TaskError, PyException);
+
+// Set while a worker runs a Python task on this thread.
+thread_local! {
+    static TASKS: RefCell<Option<Tasks>> = const { RefCell::new(None) };
+}
+
+fn in_task<R>(tasks: Tasks, f: impl FnOnce() -> R) -> R {
+    TASKS.with(|t| *t.borrow_mut() = Some(tasks));
+    let r = f();
+    TASKS.with(|t| t.borrow_mut().take());
+    r
+}
+
+/// Runs `f` on the tasks of the current Python task without holding the GIL,
+/// so the tasks it waits for can run Python.
+fn with_tasks<R: Send>(
+    py: Python<'_>,
+    f: impl FnOnce(&mut Tasks) -> Result<R, String> + Send,
+) -> PyResult<R> {
+    TASKS.with(|tasks| {
+        let mut tasks = tasks.borrow_mut();
+        let tasks = tasks.as_mut().ok_or_else(|| {
+            PyRuntimeError::new_err("dfut is only available in tasks run by a worker")
+        })?;
+        py.allow_threads(|| f(tasks))
+            .map_err(PyRuntimeError::new_err)
+    })
+}
+
+fn extract_kwargs(kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<HashMap<String, Value>> {
+    Ok(kwargs.map(|k| k.extract()).transpose()?.unwrap_or_default())
+}
+
+#[pyfunction]
+#[pyo3(name = "run_py", signature = (f_name, script, /, **kwargs))]
+fn dfut_run_py(
+    py: Python<'_>,
+    f_name: String,
+    script: String,
+    kwargs: Option<&Bound<'_, PyDict>>,
+) -> PyResult<u64> {
+    let kwargs = extract_kwargs(kwargs)?;
+    with_tasks(py, |tasks| {
+        tasks.submit(Task::RunPy {
+            f_name,
+            script,
+            kwargs,
+        })
+    })
+}
+
+#[pyfunction]
+#[pyo3(name = "register_script")]
+fn dfut_register_script(py: Python<'_>, script: String) -> PyResult<u64> {
+    with_tasks(py, |tasks| tasks.submit(Task::RegisterScript { script }))
+}
+
+#[pyfunction]
+#[pyo3(name = "call_py", signature = (script_id, f_name, /, **kwargs))]
+fn dfut_call_py(
+    py: Python<'_>,
+    script_id: &str,
+    f_name: String,
+    kwargs: Option<&Bound<'_, PyDict>>,
+) -> PyResult<u64> {
+    let script_id = parse_script_id(script_id).map_err(PyValueError::new_err)?;
+    let kwargs = extract_kwargs(kwargs)?;
+    with_tasks(py, |tasks| {
+        tasks.submit(Task::CallPy {
+            script_id,
+            f_name,
+            kwargs,
+        })
+    })
+}
+
+#[pyfunction]
+#[pyo3(name = "d_await")]
+fn dfut_d_await(py: Python<'_>, handle: u64) -> PyResult<PyObject> {
+    match with_tasks(py, |tasks| tasks.d_await(handle))? {
+        Ok(v) => Ok(v.to_object(py)),
+        Err(e) => Err(TaskError::new_err(e.to_string())),
+    }
+}
+
+#[pyfunction]
+#[pyo3(name = "d_cancel")]
+fn dfut_d_cancel(py: Python<'_>, handle: u64) -> PyResult<()> {
+    with_tasks(py, |tasks| tasks.d_cancel(handle))
+}
+
+/// Makes `import dfut` work in scripts.
+fn add_dfut_module(py: Python<'_>) -> PyResult<()> {
+    let modules = py.import_bound("sys")?.getattr("modules")?;
+    if modules.contains("dfut")? {
+        return Ok(());
+    }
+    let module = PyModule::new_bound(py, "dfut")?;
+    module.add_function(wrap_pyfunction!(dfut_run_py, &module)?)?;
+    module.add_function(wrap_pyfunction!(dfut_register_script, &module)?)?;
+    module.add_function(wrap_pyfunction!(dfut_call_py, &module)?)?;
+    module.add_function(wrap_pyfunction!(dfut_d_await, &module)?)?;
+    module.add_function(wrap_pyfunction!(dfut_d_cancel, &module)?)?;
+    module.add("TaskError", py.get_type_bound::<TaskError>())?;
+    modules.set_item("dfut", module)
+}
+
+#[derive(Debug, Clone)]
+pub struct Worker {
+    runtime: Runtime,
+}
+
+// Python runs on the blocking pool, or in the subprocesses of `py_pool` if
+// one is installed, so it never blocks the runtime's networking. Scripts can
+// `import dfut` to start and await tasks on this worker.
+#[into_dfut]
+impl Worker {
+    /// Compiles the script on every call, see `call_py` for repeated calls.
+    pub async fn run_py(
+        &self,
+        f_name: String,
+        script: String,
+        kwargs: HashMap<String, Value>,
+    ) -> DResult<Result<Value, PyError>> {
+        if let Some(pool) = py_pool::installed() {
+            return Ok(pool.run_py(self.clone(), f_name, script, kwargs).await);
+        }
+        let tasks = Tasks::new(self.clone());
+        Ok(tokio::task::spawn_blocking(move || {
+            in_task(tasks, || run_py(&f_name, &script, &kwargs))
+        })
+        .await
+        .unwrap())
+    }
+
+    /// Compiles the script in this worker process, so that it can be called
+    /// by id with `call_py`.
+    pub async fn register_script(&self, script: String) -> DResult<Result<ScriptId, PyError>> {
+        remember(&Script::new(script.as_str()));
+        if let Some(pool) = py_pool::installed() {
+            return Ok(pool.register_script(self.clone(), script).await);
+        }
+        let tasks = Tasks::new(self.clone());
+        Ok(
+            tokio::task::spawn_blocking(move || in_task(tasks, || register_script(&script)))
+                .await
+                .unwrap(),
+        )
+    }
+
+    /// Calls `f_name(**kwargs)` from a script this worker process has seen,
+    /// e.g. through `register_script`, without the script's text. Fails with
+    /// `PyError::unknown_script` for other scripts, see `call_by_id`.
+    pub async fn call_py(
+        &self,
+        script_id: ScriptId,
+        f_name: String,
+        kwargs: HashMap<String, Value>,
+    ) -> DResult<Result<Value, PyError>> {
+        match known_script(script_id) {
+            Some(script) => Ok(call_known(self.clone(), script, f_name, kwargs).await),
+            None => Ok(Err(PyError::unknown_script(script_id))),
+        }
+    }
+
+    /// Like `call_py` for a script this worker process may not have seen. It
+    /// is cached under the hash of `script` for later calls by id.
+    pub async fn load_and_call_py(
+        &self,
+        script: String,
+        f_name: String,
+        kwargs: HashMap<String, Value>,
+    ) -> DResult<Result<Value, PyError>> {
+        let script = Script::new(script);
+        remember(&script);
+        Ok(call_known(self.clone(), script, f_name, kwargs).await)
+    }
+}
+
+async fn call_known(
+    worker: Worker,
+    script: Script,
+    f_name: String,
+    kwargs: HashMap<String, Value>,
+) -> Result<Value, PyError> {
+    if let Some(pool) = py_pool::installed() {
+        return pool.call_py(worker, script, f_name, kwargs).await;
+    }
+    let tasks = Tasks::new(worker);
+    tokio::task::spawn_blocking(move || in_task(tasks, || call_py(&script, &f_name, &kwargs)))
+        .await
+        .unwrap()
+}
+
+/// Calls `f_name(**kwargs)` from `script` by id, and sends the text only if
+/// the worker process running the call doesn't know the script.
+pub async fn call_by_id(
+    client: &WorkerClient,
+    script: &Script,
+    f_name: &str,
+    kwargs: HashMap<String, Value>,
+) -> DResult<Result<Value, PyError>> {
+    let f = client
+        .call_py(script.id, f_name.to_string(), kwargs.clone())
+        .await?;
+    match client.d_await(f).await? {
+        Err(e) if e.is_unknown_script() => {
+            let f = client
+                .load_and_call_py(script.text.clone(), f_name.to_string(), kwargs)
+                .await?;
+            client.d_await(f).await
+        }
+        result => Ok(result),
+    }
+}
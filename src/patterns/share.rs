@@ -0,0 +1,374 @@
+use std::fmt;
+use std::str::FromStr;
+
+use dfut::{d_await, d_box, d_cancel, into_dfut, DFut, DResult, Runtime};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Model {
+    /// Least squares, the loss is half the mean squared error.
+    Linear,
+    /// Labels in `{0, 1}`, the loss is the mean log loss.
+    Logistic,
+}
+
+impl Model {
+    pub const ALL: &'static [Model] = &[Model::Linear, Model::Logistic];
+
+    fn predict(&self, weights: &[f64], x: &[f64]) -> f64 {
+        let (bias, w) = weights.split_last().unwrap();
+        let z = bias + w.iter().zip(x).map(|(w, x)| w * x).sum::<f64>();
+        match self {
+            Model::Linear => z,
+            Model::Logistic => 1. / (1. + (-z).exp()),
+        }
+    }
+
+    fn loss(&self, p: f64, y: f64) -> f64 {
+        match self {
+            Model::Linear => 0.5 * (p - y).powi(2),
+            Model::Logistic => {
+                let p = p.clamp(1e-12, 1. - 1e-12);
+                -(y * p.ln() + (1. - y) * (1. - p).ln())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Model {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Model::Linear => "linear",
+            Model::Logistic => "logistic",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for Model {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Model::ALL
+            .iter()
+            .find(|m| m.to_string() == s)
+            .copied()
+            .ok_or_else(|| format!("unknown model: {s}"))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Dataset {
+    pub x: Vec<Vec<f64>>,
+    pub y: Vec<f64>,
+}
+
+impl Dataset {
+    /// Features uniform in `[-1, 1]` with labels from random true weights,
+    /// plus noise for `Linear` and Bernoulli sampling for `Logistic`.
+    pub fn synthetic(model: Model, n_samples: usize, n_features: usize, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut weights: Vec<f64> = (0..n_features).map(|_| rng.gen_range(-2.0..2.0)).collect();
+        weights.push(0.5);
+
+        let mut data = Dataset::default();
+        for _ in 0..n_samples {
+            let x: Vec<f64> = (0..n_features).map(|_| rng.gen_range(-1.0..1.0)).collect();
+            let p = model.predict(&weights, &x);
+            let y = match model {
+                Model::Linear => p + rng.gen_range(-0.1..0.1),
+                Model::Logistic => (rng.gen::<f64>() < p) as u8 as f64,
+            };
+            data.x.push(x);
+            data.y.push(y);
+        }
+        data
+    }
+
+    pub fn len(&self) -> usize {
+        self.y.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.y.is_empty()
+    }
+
+    pub fn n_features(&self) -> usize {
+        self.x.first().map_or(0, Vec::len)
+    }
+
+    /// Splits into `n` contiguous shards of nearly equal size.
+    pub fn shards(self, n: usize) -> Vec<Dataset> {
+        let len = self.len();
+        let mut x = self.x.into_iter();
+        let mut y = self.y.into_iter();
+        (0..n)
+            .map(|i| {
+                let size = (i + 1) * len / n - i * len / n;
+                Dataset {
+                    x: x.by_ref().take(size).collect(),
+                    y: y.by_ref().take(size).collect(),
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HyperParams {
+    pub learning_rate: f64,
+    /// L2 penalty on the weights, not on the bias.
+    pub l2: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SearchCfg {
+    pub epochs: u64,
+    /// The dataset is split into this many shards, each epoch computes one
+    /// gradient per shard.
+    pub n_shards: u64,
+    /// After each epoch, trials whose loss is above `stop_ratio` times the
+    /// best loss (or not finite) are stopped.
+    pub stop_ratio: f64,
+}
+
+impl Default for SearchCfg {
+    fn default() -> Self {
+        Self {
+            epochs: 50,
+            n_shards: 4,
+            stop_ratio: 2.,
+        }
+    }
+}
+
+/// Sum over a shard, divided by the total number of samples by the caller.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Gradient {
+    pub grad: Vec<f64>,
+    pub loss: f64,
+    pub n: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Epoch {
+    pub weights: Vec<f64>,
+    /// Loss of the weights the epoch started from.
+    pub loss: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trial {
+    pub hyperparams: HyperParams,
+    /// Weights per feature followed by the bias.
+    pub weights: Vec<f64>,
+    /// Loss of `weights` on the whole dataset.
+    pub loss: f64,
+    pub epochs: u64,
+    pub stopped: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub best: Trial,
+    pub trials: Vec<Trial>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Worker {
+    runtime: Runtime,
+}
+
+#[into_dfut]
+impl Worker {
+    // Supervisor. Inspired by:
+    // https://docs.ray.io/en/latest/ray-core/patterns/tree-of-actors.html.
+    //
+    // Trains one model per hyperparameter setting with full batch gradient
+    // descent. Every epoch shares the shards once per trial still running.
+    pub async fn search(
+        &self,
+        model: Model,
+        hyperparams: Vec<HyperParams>,
+        data: Dataset,
+        cfg: SearchCfg,
+    ) -> DResult<SearchResult> {
+        let n_features = data.n_features();
+        let shards: Vec<_> = data
+            .shards(cfg.n_shards.max(1) as usize)
+            .into_iter()
+            .map(|shard| d_box!(shard))
+            .collect();
+
+        let mut trials: Vec<Trial> = hyperparams
+            .into_iter()
+            .map(|hyperparams| Trial {
+                hyperparams,
+                weights: vec![0.; n_features + 1],
+                loss: f64::INFINITY,
+                epochs: 0,
+                stopped: false,
+            })
+            .collect();
+
+        for _ in 0..cfg.epochs {
+            let live: Vec<_> = (0..trials.len()).filter(|&i| !trials[i].stopped).collect();
+            let handles = share(&self.runtime, &shards, live.len()).await?;
+            let mut epochs = Vec::new();
+            for (i, shards) in live.into_iter().zip(handles) {
+                let trial = &trials[i];
+                let f = self
+                    .train_epoch(model, trial.hyperparams, trial.weights.clone(), shards)
+                    .await?;
+                epochs.push((i, f));
+            }
+            for (i, f) in epochs {
+                let epoch = d_await!(f);
+                trials[i].weights = epoch.weights;
+                trials[i].loss = epoch.loss;
+                trials[i].epochs += 1;
+            }
+
+            let best = trials
+                .iter()
+                .filter(|t| !t.stopped)
+                .map(|t| t.loss)
+                .fold(f64::INFINITY, f64::min);
+            for trial in trials.iter_mut().filter(|t| !t.stopped) {
+                trial.stopped = trial.loss > best * cfg.stop_ratio;
+            }
+        }
+
+        let live: Vec<_> = (0..trials.len()).filter(|&i| !trials[i].stopped).collect();
+        let handles = share(&self.runtime, &shards, live.len()).await?;
+        for shard in shards {
+            d_cancel!(shard);
+        }
+        let mut evaluations = Vec::new();
+        for (i, shards) in live.into_iter().zip(handles) {
+            let trial = &trials[i];
+            evaluations.push((
+                i,
+                self.evaluate(model, trial.weights.clone(), shards).await?,
+            ));
+        }
+        for (i, f) in evaluations {
+            trials[i].loss = d_await!(f);
+        }
+
+        let best = trials
+            .iter()
+            .filter(|t| !t.stopped)
+            .min_by(|a, b| a.loss.total_cmp(&b.loss))
+            .or_else(|| trials.iter().min_by(|a, b| a.loss.total_cmp(&b.loss)))
+            .cloned()
+            .ok_or(dfut::Error::System)?;
+        Ok(SearchResult { best, trials })
+    }
+
+    /// One gradient step, averaging the gradients of all shards.
+    pub async fn train_epoch(
+        &self,
+        model: Model,
+        hyperparams: HyperParams,
+        mut weights: Vec<f64>,
+        shards: Vec<DFut<Dataset>>,
+    ) -> DResult<Epoch> {
+        let total = d_await!(self.total_gradient(model, weights.clone(), shards).await?);
+        let n = total.n.max(1) as f64;
+
+        let bias = weights.len() - 1;
+        for (i, (w, g)) in weights.iter_mut().zip(&total.grad).enumerate() {
+            let l2 = if i == bias { 0. } else { hyperparams.l2 * *w };
+            *w -= hyperparams.learning_rate * (g / n + l2);
+        }
+        Ok(Epoch {
+            weights,
+            loss: total.loss / n,
+        })
+    }
+
+    pub async fn evaluate(
+        &self,
+        model: Model,
+        weights: Vec<f64>,
+        shards: Vec<DFut<Dataset>>,
+    ) -> DResult<f64> {
+        let total = d_await!(self.total_gradient(model, weights, shards).await?);
+        Ok(total.loss / total.n.max(1) as f64)
+    }
+
+    /// Sums the gradients of all shards.
+    pub async fn total_gradient(
+        &self,
+        model: Model,
+        weights: Vec<f64>,
+        shards: Vec<DFut<Dataset>>,
+    ) -> DResult<Gradient> {
+        let mut gradients = Vec::new();
+        for shard in shards {
+            gradients.push(self.gradient(model, weights.clone(), shard).await?);
+        }
+
+        let mut total = Gradient {
+            grad: vec![0.; weights.len()],
+            ..Default::default()
+        };
+        for f in gradients {
+            let g = d_await!(f);
+            for (t, g) in total.grad.iter_mut().zip(g.grad) {
+                *t += g;
+            }
+            total.loss += g.loss;
+            total.n += g.n;
+        }
+        Ok(total)
+    }
+
+    pub async fn gradient(
+        &self,
+        model: Model,
+        weights: Vec<f64>,
+        shard: DFut<Dataset>,
+    ) -> DResult<Gradient> {
+        let shard = d_await!(shard);
+        let mut out = Gradient {
+            grad: vec![0.; weights.len()],
+            loss: 0.,
+            n: shard.len() as u64,
+        };
+        for (x, y) in shard.x.iter().zip(&shard.y) {
+            let p = model.predict(&weights, x);
+            out.loss += model.loss(p, *y);
+            // Both losses have the gradient `(p - y) * x` w.r.t. the weights.
+            let err = p - y;
+            let (bias, grad) = out.grad.split_last_mut().unwrap();
+            for (g, x) in grad.iter_mut().zip(x) {
+                *g += err * x;
+            }
+            *bias += err;
+        }
+        Ok(out)
+    }
+}
+
+/// Shares every shard `n` times, returning the shard handles of each of the
+/// `n` consumers.
+async fn share(
+    runtime: &Runtime,
+    shards: &[DFut<Dataset>],
+    n: usize,
+) -> DResult<Vec<Vec<DFut<Dataset>>>> {
+    let mut handles: Vec<_> = (0..n).map(|_| Vec::with_capacity(shards.len())).collect();
+    for shard in shards {
+        for (handles, f) in handles
+            .iter_mut()
+            .zip(runtime.share_n(shard, n as u64).await?)
+        {
+            handles.push(f);
+        }
+    }
+    Ok(handles)
+}
@@ -0,0 +1,355 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use dfut::{d_await, into_dfut, DFut, DResult, Runtime};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rand_distr::Zipf;
+use serde::{Deserialize, Serialize};
+
+use crate::fault;
+
+/// The default `QuickSortCfg::cutoff`.
+const SEQUENTIAL_CUTOFF: usize = 200_000;
+
+/// Samples taken per bucket when choosing sample sort splitters.
+const OVERSAMPLE: usize = 32;
+
+/// How `partition` picks its pivot.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Pivot {
+    /// The last element.
+    #[default]
+    Last,
+    /// The median of the first, middle and last elements.
+    MedianOfThree,
+    Random,
+}
+
+impl Pivot {
+    pub const ALL: &'static [Pivot] = &[Pivot::Last, Pivot::MedianOfThree, Pivot::Random];
+
+    fn index(&self, v: &[u64]) -> usize {
+        let last = v.len() - 1;
+        match self {
+            Pivot::Last => last,
+            Pivot::MedianOfThree => {
+                let mut i = [0, last / 2, last];
+                i.sort_by_key(|i| v[*i]);
+                i[1]
+            }
+            Pivot::Random => rand::thread_rng().gen_range(0..v.len()),
+        }
+    }
+}
+
+impl fmt::Display for Pivot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Pivot::Last => "last",
+            Pivot::MedianOfThree => "median-of-three",
+            Pivot::Random => "random",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for Pivot {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Pivot::ALL
+            .iter()
+            .find(|p| p.to_string() == s)
+            .copied()
+            .ok_or_else(|| format!("unknown pivot: {s}"))
+    }
+}
+
+/// Parameters of `quick_sort` and `local_quick_sort`. `merge_sort` and
+/// `sample_sort` take the cutoff only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuickSortCfg {
+    /// Inputs shorter than this are sorted locally instead of split.
+    pub cutoff: u64,
+    pub pivot: Pivot,
+    /// Partition on tokio's blocking pool instead of the worker's runtime.
+    pub blocking_partition: bool,
+}
+
+impl Default for QuickSortCfg {
+    fn default() -> Self {
+        Self {
+            cutoff: SEQUENTIAL_CUTOFF as u64,
+            pivot: Pivot::default(),
+            blocking_partition: true,
+        }
+    }
+}
+
+pub fn partition(mut v: Vec<u64>, pivot: Pivot) -> (Vec<u64>, u64, Vec<u64>) {
+    let i = pivot.index(&v);
+    let p = v.swap_remove(i);
+    let mut l = Vec::new();
+    let mut g = Vec::new();
+    for e in v {
+        if e > p {
+            g.push(e);
+        } else {
+            l.push(e);
+        }
+    }
+
+    (l, p, g)
+}
+
+/// The result of [`partition_or_sort`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Split {
+    Partitioned(Vec<u64>, u64, Vec<u64>),
+    /// The pivot was the smallest or largest element, e.g. `Pivot::Last` on
+    /// sorted input or on equal elements. Recursing on such a partition only
+    /// peels off one element per level, so the input was sorted instead.
+    Sorted(Vec<u64>),
+}
+
+// Counts the `Split::Sorted` results of `partition_or_sort`.
+static SORTED_SPLITS: AtomicU64 = AtomicU64::new(0);
+
+/// Partitions in this process that split nothing off, so that
+/// `partition_or_sort` sorted their input instead. Experiments report it, a
+/// degenerate pivot would otherwise only show as a faster sort.
+pub fn sorted_splits() -> u64 {
+    SORTED_SPLITS.load(Ordering::Relaxed)
+}
+
+/// Like [`partition`], but sorts `v` if one side of the partition is empty.
+pub fn partition_or_sort(v: Vec<u64>, pivot: Pivot) -> Split {
+    let (mut l, p, g) = partition(v, pivot);
+    if !l.is_empty() && !g.is_empty() {
+        return Split::Partitioned(l, p, g);
+    }
+    SORTED_SPLITS.fetch_add(1, Ordering::Relaxed);
+    l.push(p);
+    l.extend(g);
+    l.sort();
+    Split::Sorted(l)
+}
+
+pub fn local_quick_sort(mut v: Vec<u64>, cfg: &QuickSortCfg) -> Vec<u64> {
+    if (v.len() as u64) < cfg.cutoff.max(1) {
+        v.sort();
+        return v;
+    }
+    let (l, p, g) = match partition_or_sort(v, cfg.pivot) {
+        Split::Partitioned(l, p, g) => (l, p, g),
+        Split::Sorted(v) => return v,
+    };
+    let l = local_quick_sort(l, cfg);
+    let g = local_quick_sort(g, cfg);
+    let mut out = Vec::new();
+    out.extend(l);
+    out.push(p);
+    out.extend(g);
+    out
+}
+
+pub fn merge(l: Vec<u64>, g: Vec<u64>) -> Vec<u64> {
+    let mut out = Vec::with_capacity(l.len() + g.len());
+    let mut l = l.into_iter().peekable();
+    let mut g = g.into_iter().peekable();
+    while let (Some(a), Some(b)) = (l.peek(), g.peek()) {
+        if a <= b {
+            out.push(l.next().unwrap());
+        } else {
+            out.push(g.next().unwrap());
+        }
+    }
+    out.extend(l);
+    out.extend(g);
+    out
+}
+
+/// Picks `n_buckets - 1` splitters from a random sample of `v`.
+pub fn splitters(v: &[u64], n_buckets: usize) -> Vec<u64> {
+    let mut rng = rand::thread_rng();
+    let mut sample: Vec<u64> = (0..n_buckets * OVERSAMPLE)
+        .map(|_| v[rng.gen_range(0..v.len())])
+        .collect();
+    sample.sort_unstable();
+    let mut splitters: Vec<u64> = (1..n_buckets).map(|i| sample[i * OVERSAMPLE]).collect();
+    splitters.dedup();
+    splitters
+}
+
+/// Bucket `i` holds the elements in `(splitters[i - 1], splitters[i]]`.
+pub fn bucketize(v: Vec<u64>, splitters: &[u64]) -> Vec<Vec<u64>> {
+    let mut buckets = vec![Vec::new(); splitters.len() + 1];
+    for e in v {
+        buckets[splitters.partition_point(|s| *s < e)].push(e);
+    }
+    buckets
+}
+
+/// Input distributions for the sort benchmarks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Distribution {
+    /// A random permutation of `0..size`.
+    Uniform,
+    Sorted,
+    Reverse,
+    /// Zipf distributed values (s = 1.1) over `1..=size`.
+    Zipf,
+    /// Only 16 distinct values.
+    Duplicates,
+}
+
+impl Distribution {
+    pub const ALL: &'static [Distribution] = &[
+        Distribution::Uniform,
+        Distribution::Sorted,
+        Distribution::Reverse,
+        Distribution::Zipf,
+        Distribution::Duplicates,
+    ];
+
+    pub fn generate(&self, size: u64) -> Vec<u64> {
+        let mut rng = rand::thread_rng();
+        match self {
+            Distribution::Uniform => {
+                let mut v: Vec<u64> = (0..size).collect();
+                v.shuffle(&mut rng);
+                v
+            }
+            Distribution::Sorted => (0..size).collect(),
+            Distribution::Reverse => (0..size).rev().collect(),
+            Distribution::Zipf => {
+                let zipf = Zipf::new(size.max(1), 1.1).unwrap();
+                (0..size).map(|_| rng.sample(zipf) as u64).collect()
+            }
+            Distribution::Duplicates => (0..size).map(|_| rng.gen_range(0..16)).collect(),
+        }
+    }
+}
+
+impl fmt::Display for Distribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Distribution::Uniform => "uniform",
+            Distribution::Sorted => "sorted",
+            Distribution::Reverse => "reverse",
+            Distribution::Zipf => "zipf",
+            Distribution::Duplicates => "duplicates",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for Distribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Distribution::ALL
+            .iter()
+            .find(|d| d.to_string() == s)
+            .copied()
+            .ok_or_else(|| format!("unknown distribution: {s}"))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Worker {
+    runtime: Runtime,
+}
+
+#[into_dfut]
+impl Worker {
+    // Sort. Inspired by: https://docs.ray.io/en/latest/ray-core/patterns/nested-tasks.html.
+    pub async fn quick_sort(&self, mut v: Vec<u64>, cfg: QuickSortCfg) -> DResult<Vec<u64>> {
+        fault::inject("quick_sort").await?;
+        if (v.len() as u64) < cfg.cutoff.max(1) {
+            v.sort();
+            return Ok(v);
+        }
+
+        let split = if cfg.blocking_partition {
+            tokio::task::spawn_blocking(move || partition_or_sort(v, cfg.pivot))
+                .await
+                .unwrap()
+        } else {
+            partition_or_sort(v, cfg.pivot)
+        };
+        let (l, p, g) = match split {
+            Split::Partitioned(l, p, g) => (l, p, g),
+            Split::Sorted(v) => return Ok(v),
+        };
+
+        let l_fut = self.quick_sort(l, cfg).await?;
+        let g_fut = self.quick_sort(g, cfg).await?;
+
+        let mut out = Vec::new();
+        out.extend(d_await!(l_fut));
+        out.push(p);
+        out.extend(d_await!(g_fut));
+
+        Ok(out)
+    }
+
+    // Splits in halves, so the depth does not depend on the input.
+    pub async fn merge_sort(&self, mut v: Vec<u64>, cutoff: u64) -> DResult<Vec<u64>> {
+        fault::inject("merge_sort").await?;
+        if (v.len() as u64) < cutoff.max(2) {
+            v.sort();
+            return Ok(v);
+        }
+
+        let g = v.split_off(v.len() / 2);
+        let l_fut = self.merge_sort(v, cutoff).await?;
+        let g_fut = self.merge_sort(g, cutoff).await?;
+
+        let l = d_await!(l_fut);
+        let g = d_await!(g_fut);
+        Ok(tokio::task::spawn_blocking(move || merge(l, g))
+            .await
+            .unwrap())
+    }
+
+    // One level of `n_buckets` bucket sorts split by sampled splitters.
+    pub async fn sample_sort(
+        &self,
+        mut v: Vec<u64>,
+        n_buckets: u64,
+        cutoff: u64,
+    ) -> DResult<Vec<u64>> {
+        fault::inject("sample_sort").await?;
+        if (v.len() as u64) < cutoff.max(1) || n_buckets < 2 {
+            v.sort();
+            return Ok(v);
+        }
+
+        let buckets = tokio::task::spawn_blocking(move || {
+            let splitters = splitters(&v, n_buckets as usize);
+            bucketize(v, &splitters)
+        })
+        .await
+        .unwrap();
+
+        let mut bucket_futs = Vec::new();
+        for bucket in buckets {
+            bucket_futs.push(self.sort_bucket(bucket).await?);
+        }
+
+        let mut out = Vec::new();
+        for bucket_fut in bucket_futs {
+            out.extend(d_await!(bucket_fut));
+        }
+        Ok(out)
+    }
+
+    pub async fn sort_bucket(&self, mut v: Vec<u64>) -> DResult<Vec<u64>> {
+        fault::inject("sort_bucket").await?;
+        v.sort_unstable();
+        Ok(v)
+    }
+}
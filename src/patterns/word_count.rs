@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use dfut::{d_await, d_cancel, into_dfut, DFut, DResult, Runtime};
+use serde::{Deserialize, Serialize};
+
+use crate::fault;
+
+/// Intermediate values of one partition, keyed by word.
+pub type Partition = HashMap<String, Vec<u64>>;
+
+/// Map side pre-aggregation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Combiner {
+    /// Emit a `1` per occurrence, the reducers see every occurrence.
+    None,
+    /// Emit one count per word and shard.
+    #[default]
+    Sum,
+}
+
+impl Combiner {
+    pub const ALL: &'static [Combiner] = &[Combiner::None, Combiner::Sum];
+
+    pub fn combine(&self, values: &mut Vec<u64>) {
+        match self {
+            Combiner::None => {}
+            Combiner::Sum => {
+                let sum = values.iter().sum();
+                values.clear();
+                values.push(sum);
+            }
+        }
+    }
+}
+
+impl fmt::Display for Combiner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Combiner::None => "none",
+            Combiner::Sum => "sum",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for Combiner {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Combiner::ALL
+            .iter()
+            .find(|c| c.to_string() == s)
+            .copied()
+            .ok_or_else(|| format!("unknown combiner: {s}"))
+    }
+}
+
+/// Lowercased runs of alphanumeric characters.
+pub fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+}
+
+// FNV-1a, so that workers built by different Rust releases agree on the
+// partition of a word too.
+pub fn partition_of(word: &str, n_partitions: u64) -> usize {
+    (fault::fnv1a(word) % n_partitions) as usize
+}
+
+/// Reads every file of `dir` as one shard, sorted by file name.
+pub fn read_shards(dir: impl AsRef<Path>) -> std::io::Result<Vec<String>> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            paths.push(entry.path());
+        }
+    }
+    paths.sort();
+    paths.into_iter().map(std::fs::read_to_string).collect()
+}
+
+/// Reference result computed on a single thread.
+pub fn local_word_count(shards: &[String]) -> HashMap<String, u64> {
+    let mut counts = HashMap::new();
+    for shard in shards {
+        for word in words(shard) {
+            *counts.entry(word).or_default() += 1;
+        }
+    }
+    counts
+}
+
+#[derive(Debug, Clone)]
+pub struct Worker {
+    runtime: Runtime,
+}
+
+#[into_dfut]
+impl Worker {
+    pub async fn word_count(
+        &self,
+        shards: Vec<String>,
+        n_partitions: u64,
+        combiner: Combiner,
+    ) -> DResult<HashMap<String, u64>> {
+        let n_partitions = n_partitions.max(1);
+
+        // Each map output is shared once per partition so that every reducer
+        // can select its own part.
+        let mut map_outs = Vec::new();
+        for shard in shards {
+            let f = self.map(shard, n_partitions, combiner).await?;
+            map_outs.push(self.runtime.share_n(&f, n_partitions).await?);
+            d_cancel!(f);
+        }
+
+        let mut partitions: Vec<Vec<DFut<Partition>>> =
+            (0..n_partitions).map(|_| Vec::new()).collect();
+        for map_out in map_outs {
+            for (p, f) in map_out.into_iter().enumerate() {
+                partitions[p].push(self.select(f, p as u64).await?);
+            }
+        }
+
+        let mut reduced = Vec::new();
+        for partition in partitions {
+            reduced.push(self.reduce(partition).await?);
+        }
+
+        let mut out = HashMap::new();
+        for f in reduced {
+            out.extend(d_await!(f));
+        }
+        Ok(out)
+    }
+
+    /// Splits the words of a shard into `n_partitions` by hash.
+    pub async fn map(
+        &self,
+        shard: String,
+        n_partitions: u64,
+        combiner: Combiner,
+    ) -> DResult<Vec<Partition>> {
+        let mut out: Vec<Partition> = (0..n_partitions).map(|_| HashMap::new()).collect();
+        for word in words(&shard) {
+            let p = partition_of(&word, n_partitions);
+            out[p].entry(word).or_default().push(1);
+        }
+        for partition in &mut out {
+            for values in partition.values_mut() {
+                combiner.combine(values);
+            }
+        }
+        Ok(out)
+    }
+
+    pub async fn select(&self, map_out: DFut<Vec<Partition>>, p: u64) -> DResult<Partition> {
+        let mut map_out = d_await!(map_out);
+        Ok(map_out.swap_remove(p as usize))
+    }
+
+    pub async fn reduce(&self, parts: Vec<DFut<Partition>>) -> DResult<HashMap<String, u64>> {
+        let mut counts: HashMap<String, u64> = HashMap::new();
+        for part in parts {
+            for (word, values) in d_await!(part) {
+                *counts.entry(word).or_default() += values.iter().sum::<u64>();
+            }
+        }
+        Ok(counts)
+    }
+}
@@ -0,0 +1,179 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// A small nested struct, like the rows workers exchange.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub id: u64,
+    pub name: String,
+    pub tags: Vec<String>,
+    pub point: Point,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// How a payload is passed to a `NoOpWorker`, see `payload-bench`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Shape {
+    /// `Vec<u8>` to `echo_bytes`.
+    Bytes,
+    /// `Vec<u64>` to `echo_u64s`.
+    U64s,
+    /// `Vec<Record>` to `echo_records`.
+    Records,
+    /// `Vec<String>` to `echo_strings`.
+    Strings,
+    /// Bytes produced by `nop` on a worker and passed to `len_of` as a
+    /// `DFut`, without going through the caller. Unlike the others, this
+    /// makes two calls.
+    DFut,
+}
+
+impl Shape {
+    pub const ALL: &'static [Shape] = &[
+        Shape::Bytes,
+        Shape::U64s,
+        Shape::Records,
+        Shape::Strings,
+        Shape::DFut,
+    ];
+
+    /// A payload of about `n_bytes` when encoded, with at least one element
+    /// even if that is larger.
+    pub fn payload(&self, n_bytes: usize) -> Payload {
+        match self {
+            Shape::Bytes | Shape::DFut => Payload::Bytes(vec![42; n_bytes]),
+            Shape::U64s => Payload::U64s((0..(n_bytes as u64 / 8).max(1)).collect()),
+            Shape::Records => {
+                let n = (n_bytes / encoded_len(&record(0))).max(1);
+                Payload::Records((0..n as u64).map(record).collect())
+            }
+            Shape::Strings => {
+                let n = (n_bytes / encoded_len(&string(0))).max(1);
+                Payload::Strings((0..n).map(string).collect())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Shape::Bytes => "bytes",
+            Shape::U64s => "u64s",
+            Shape::Records => "records",
+            Shape::Strings => "strings",
+            Shape::DFut => "d-fut",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for Shape {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Shape::ALL
+            .iter()
+            .find(|shape| shape.to_string() == s)
+            .copied()
+            .ok_or_else(|| format!("unknown shape: {s}"))
+    }
+}
+
+fn record(i: u64) -> Record {
+    Record {
+        id: i,
+        name: format!("record-{i:09}"),
+        tags: vec!["tag-a".to_string(), "tag-b".to_string()],
+        point: Point {
+            x: i as f64,
+            y: -(i as f64),
+        },
+    }
+}
+
+fn string(i: usize) -> String {
+    format!("{i:032}")
+}
+
+fn encoded_len<T: Serialize>(v: &T) -> usize {
+    bincode::serialized_size(v).unwrap() as usize
+}
+
+/// Time to encode `v` with bincode and decode it again.
+fn round_trip<T: Serialize + DeserializeOwned>(v: &T) -> Duration {
+    let start = Instant::now();
+    let encoded = bincode::serialize(v).unwrap();
+    let decoded: T = bincode::deserialize(&encoded).unwrap();
+    let elapsed = start.elapsed();
+    drop(decoded);
+    elapsed
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Payload {
+    Bytes(Vec<u8>),
+    U64s(Vec<u64>),
+    Records(Vec<Record>),
+    Strings(Vec<String>),
+}
+
+impl Payload {
+    /// Bincode encoded size of the value, without the enum tag.
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            Payload::Bytes(v) => encoded_len(v),
+            Payload::U64s(v) => encoded_len(v),
+            Payload::Records(v) => encoded_len(v),
+            Payload::Strings(v) => encoded_len(v),
+        }
+    }
+
+    /// Median time, over `n` tries, to encode and decode the value with
+    /// bincode. Estimates the serialization share of a call, which does it
+    /// twice (argument and result).
+    pub fn codec_time(&self, n: usize) -> Duration {
+        let mut times: Vec<_> = (0..n.max(1))
+            .map(|_| match self {
+                Payload::Bytes(v) => round_trip(v),
+                Payload::U64s(v) => round_trip(v),
+                Payload::Records(v) => round_trip(v),
+                Payload::Strings(v) => round_trip(v),
+            })
+            .collect();
+        times.sort_unstable();
+        times[times.len() / 2]
+    }
+}
+
+/// Per-call and per-byte cost from `(bytes, seconds)` points, e.g. latency
+/// against payload size. The per-byte cost is the least squares slope; the
+/// per-call cost is what remains of the smallest payload's time, since a fit
+/// over doubling sizes is dominated by the largest ones. `None` without two
+/// distinct sizes.
+pub fn costs(points: &[(f64, f64)]) -> Option<(f64, f64)> {
+    if points.is_empty() {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let var_x: f64 = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+    if var_x == 0. {
+        return None;
+    }
+    let cov: f64 = points.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+    let per_byte = cov / var_x;
+
+    let (x, y) = points.iter().min_by(|a, b| a.0.total_cmp(&b.0)).unwrap();
+    Some(((y - per_byte * x).max(0.), per_byte))
+}
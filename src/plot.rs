@@ -0,0 +1,265 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use plotters::prelude::*;
+
+use crate::bench::{Labels, Sample, Summary};
+use crate::compare;
+
+/// What a [`by_label`] chart shows for each group of samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metric {
+    /// The latency quantile of the successful calls, e.g. 0.5 for the median.
+    Latency(f64),
+    /// Completed calls per second, see [`Summary::throughput`].
+    Throughput,
+}
+
+impl Metric {
+    fn describe(&self) -> String {
+        match self {
+            Metric::Latency(q) => format!("p{} latency (ms)", q * 100.),
+            Metric::Throughput => "throughput (calls/s)".to_string(),
+        }
+    }
+
+    fn of(&self, samples: &[Sample]) -> Option<f64> {
+        match self {
+            Metric::Latency(q) => {
+                let mut durs: Vec<_> = samples
+                    .iter()
+                    .filter(|s| s.is_ok())
+                    .map(|s| s.dur)
+                    .collect();
+                if durs.is_empty() {
+                    return None;
+                }
+                durs.sort_unstable();
+                Some(millis(compare::quantile(&durs, *q)))
+            }
+            Metric::Throughput => Some(Summary::new(samples).throughput()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Series {
+    pub name: String,
+    /// Sorted by `x`.
+    pub points: Vec<(f64, f64)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chart {
+    pub title: String,
+    pub x_desc: String,
+    pub y_desc: String,
+    pub series: Vec<Series>,
+}
+
+#[derive(Debug)]
+pub enum PlotError {
+    /// A sample misses the label on the x axis, or its value isn't a number.
+    Label(String),
+    Draw(String),
+}
+
+impl fmt::Display for PlotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlotError::Label(e) => write!(f, "label: {e}"),
+            PlotError::Draw(e) => write!(f, "draw: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for PlotError {}
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1e3
+}
+
+/// Groups `samples` by their labels, or the `keys` of them.
+fn group<'a>(samples: &'a [Sample], keys: Option<&[String]>) -> BTreeMap<Labels, Vec<&'a Sample>> {
+    let mut groups: BTreeMap<Labels, Vec<&Sample>> = BTreeMap::new();
+    for s in samples {
+        let labels = match keys {
+            Some(keys) => s.labels.select(keys),
+            None => s.labels.clone(),
+        };
+        groups.entry(labels).or_default().push(s);
+    }
+    groups
+}
+
+fn series_name(labels: &Labels) -> String {
+    match labels.to_string() {
+        name if name.is_empty() => "all".to_string(),
+        name => name,
+    }
+}
+
+/// The `quantile` latency of each group (by `keys`, all labels when unset)
+/// in buckets of `bucket` since the first call of all samples started, e.g.
+/// to spot warmup, hiccups or the workers killed by chaos mode.
+pub fn latency_over_time(
+    samples: &[Sample],
+    keys: Option<&[String]>,
+    quantile: f64,
+    bucket: Duration,
+) -> Chart {
+    assert!(!bucket.is_zero(), "bucket must be positive");
+    let metric = Metric::Latency(quantile);
+    let start = samples.iter().map(|s| s.t.saturating_sub(s.dur)).min();
+
+    let series = group(samples, keys)
+        .into_iter()
+        .map(|(labels, samples)| {
+            let mut buckets: BTreeMap<u64, Vec<Sample>> = BTreeMap::new();
+            for s in samples {
+                let since = s.t.saturating_sub(start.unwrap_or_default());
+                let i = (since.as_secs_f64() / bucket.as_secs_f64()) as u64;
+                buckets.entry(i).or_default().push(s.clone());
+            }
+            let points = buckets
+                .into_iter()
+                .filter_map(|(i, samples)| {
+                    let y = metric.of(&samples)?;
+                    Some((i as f64 * bucket.as_secs_f64(), y))
+                })
+                .collect();
+            Series {
+                name: series_name(&labels),
+                points,
+            }
+        })
+        .collect();
+
+    Chart {
+        title: format!("{} over time", metric.describe()),
+        x_desc: "time (s)".to_string(),
+        y_desc: metric.describe(),
+        series,
+    }
+}
+
+/// `metric` against the numeric label `x`, one series per value of the
+/// `series` labels, e.g. latency against `size` per `exp_id`, or throughput
+/// against `n_workers`.
+pub fn by_label(
+    samples: &[Sample],
+    x: &str,
+    series: &[String],
+    metric: Metric,
+) -> Result<Chart, PlotError> {
+    let mut keys = series.to_vec();
+    keys.push(x.to_string());
+
+    let mut by_series: BTreeMap<Labels, Vec<(f64, f64)>> = BTreeMap::new();
+    for (labels, samples) in group(samples, Some(&keys)) {
+        let value = labels
+            .get(x)
+            .ok_or_else(|| PlotError::Label(format!("no {x:?} in {labels}")))?;
+        let x_value: f64 = value
+            .parse()
+            .map_err(|e| PlotError::Label(format!("{x}={value:?}: {e}")))?;
+        let samples: Vec<Sample> = samples.into_iter().cloned().collect();
+        if let Some(y) = metric.of(&samples) {
+            by_series
+                .entry(labels.select(series))
+                .or_default()
+                .push((x_value, y));
+        }
+    }
+
+    let series = by_series
+        .into_iter()
+        .map(|(labels, mut points)| {
+            points.sort_by(|a, b| a.0.total_cmp(&b.0));
+            Series {
+                name: series_name(&labels),
+                points,
+            }
+        })
+        .collect();
+    Ok(Chart {
+        title: format!("{} by {x}", metric.describe()),
+        x_desc: x.to_string(),
+        y_desc: metric.describe(),
+        series,
+    })
+}
+
+/// Pads a range so that a single point (or none) still gets an axis.
+fn range(values: impl Iterator<Item = f64>) -> std::ops::Range<f64> {
+    let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+        (min.min(v), max.max(v))
+    });
+    if min > max {
+        return 0.0..1.0;
+    }
+    let pad = match (max - min) * 0.05 {
+        pad if pad > 0. => pad,
+        _ => min.abs().max(1.) * 0.05,
+    };
+    min - pad..max + pad
+}
+
+/// Renders `chart` as an SVG document of `width` by `height` pixels: a line
+/// with markers per series and a legend.
+pub fn render(chart: &Chart, (width, height): (u32, u32)) -> Result<String, PlotError> {
+    let draw = |e: &dyn fmt::Display| PlotError::Draw(e.to_string());
+
+    let points = || chart.series.iter().flat_map(|s| s.points.iter());
+    let x_range = range(points().map(|p| p.0));
+    let y_range = range(points().map(|p| p.1));
+    // Latencies and throughputs are never negative.
+    let y_range = y_range.start.max(0.)..y_range.end;
+
+    let mut svg = String::new();
+    {
+        let root = SVGBackend::with_string(&mut svg, (width, height)).into_drawing_area();
+        root.fill(&WHITE).map_err(|e| draw(&e))?;
+        let mut ctx = ChartBuilder::on(&root)
+            .caption(&chart.title, ("sans-serif", 20))
+            .margin(15)
+            .x_label_area_size(40)
+            .y_label_area_size(70)
+            .build_cartesian_2d(x_range, y_range)
+            .map_err(|e| draw(&e))?;
+        ctx.configure_mesh()
+            .x_desc(&chart.x_desc)
+            .y_desc(&chart.y_desc)
+            .draw()
+            .map_err(|e| draw(&e))?;
+
+        for (i, series) in chart.series.iter().enumerate() {
+            let color = Palette99::pick(i).to_rgba();
+            ctx.draw_series(LineSeries::new(
+                series.points.iter().copied(),
+                color.stroke_width(2),
+            ))
+            .map_err(|e| draw(&e))?
+            .label(&series.name)
+            .legend(move |(x, y)| PathElement::new([(x, y), (x + 20, y)], color.stroke_width(2)));
+            ctx.draw_series(
+                series
+                    .points
+                    .iter()
+                    .map(|&p| Circle::new(p, 3, color.filled())),
+            )
+            .map_err(|e| draw(&e))?;
+        }
+
+        if !chart.series.is_empty() {
+            ctx.configure_series_labels()
+                .background_style(WHITE.mix(0.8))
+                .border_style(BLACK)
+                .draw()
+                .map_err(|e| draw(&e))?;
+        }
+        root.present().map_err(|e| draw(&e))?;
+    }
+    Ok(svg)
+}
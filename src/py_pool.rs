@@ -0,0 +1,434 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+
+use crate::patterns::py::{PyError, Script, ScriptId, Task, Tasks, Value, Worker};
+
+/// Serves JSON line requests on stdin. Scripts print to stderr, so their
+/// output can't corrupt the responses. While a script runs, its `dfut` calls
+/// are sent as messages and answered on stdin before the final `Done`.
+const SERVER: &str = r#"
+import json, math, os, sys, traceback, types
+
+out = os.fdopen(os.dup(1), 'w')
+os.dup2(2, 1)
+sys.stdout = sys.stderr
+
+modules = {}
+
+def to_py(v):
+    if v == 'None':
+        return None
+    (tag, x), = v.items()
+    if tag == 'List':
+        return [to_py(e) for e in x]
+    if tag == 'Dict':
+        return {k: to_py(e) for k, e in x.items()}
+    if tag == 'Float':
+        # 'nan', 'inf' or '-inf', see `Value::Float`.
+        return float(x)
+    return x
+
+def from_py(x):
+    if x is None:
+        return 'None'
+    if isinstance(x, bool):
+        return {'Bool': x}
+    if isinstance(x, int):
+        if not -2**63 <= x < 2**63:
+            raise OverflowError('Python int too large to convert to C long')
+        return {'Int': x}
+    if isinstance(x, float):
+        return {'Float': x if math.isfinite(x) else str(x)}
+    if isinstance(x, str):
+        return {'Str': x}
+    if isinstance(x, (list, tuple)):
+        return {'List': [from_py(e) for e in x]}
+    if isinstance(x, dict):
+        for k in x:
+            if not isinstance(k, str):
+                raise TypeError(f"'{type(k).__name__}' object cannot be converted to 'PyString'")
+        return {'Dict': {k: from_py(e) for k, e in x.items()}}
+    raise TypeError(f'unsupported type: {type(x).__name__}')
+
+def send(msg):
+    out.write(json.dumps(msg) + '\n')
+    out.flush()
+
+def nested(msg):
+    send(msg)
+    (tag, x), = json.loads(sys.stdin.readline()).items()
+    if tag == 'Err':
+        raise RuntimeError(x)
+    return x
+
+class TaskError(Exception):
+    pass
+
+def run_py(f_name, script, /, **kwargs):
+    kwargs = {k: from_py(v) for k, v in kwargs.items()}
+    return nested({'Submit': {'RunPy': {'f_name': f_name, 'script': script, 'kwargs': kwargs}}})
+
+def register_script(script):
+    return nested({'Submit': {'RegisterScript': {'script': script}}})
+
+def call_py(script_id, f_name, /, **kwargs):
+    kwargs = {k: from_py(v) for k, v in kwargs.items()}
+    return nested({'Submit': {'CallPy': {'script_id': int(script_id, 16), 'f_name': f_name, 'kwargs': kwargs}}})
+
+def d_await(handle):
+    (tag, x), = nested({'Await': handle}).items()
+    if tag == 'Err':
+        raise TaskError((x['traceback'] or '') + f"{x['type_name']}: {x['message']}")
+    return to_py(x)
+
+def d_cancel(handle):
+    nested({'Cancel': handle})
+
+dfut = types.ModuleType('dfut')
+for f in [run_py, register_script, call_py, d_await, d_cancel, TaskError]:
+    setattr(dfut, f.__name__, f)
+sys.modules['dfut'] = dfut
+
+def compile_module(script, file_name):
+    module = types.ModuleType('')
+    exec(compile(script, file_name, 'exec'), module.__dict__)
+    return module
+
+def call(module, f_name, kwargs):
+    f = getattr(module, f_name)
+    return from_py(f(**{k: to_py(v) for k, v in kwargs.items()}))
+
+def handle(op, req):
+    if op == 'Run':
+        return call(compile_module(req['script'], '<script>'), req['f_name'], req['kwargs'])
+    if op == 'Register':
+        script_id = req['script_id']
+        if script_id not in modules:
+            modules[script_id] = compile_module(req['script'], f'<script {script_id:016x}>')
+        return script_id
+    if op == 'Call':
+        return call(modules[req['script_id']], req['f_name'], req['kwargs'])
+    raise ValueError(f'unknown op: {op}')
+
+while line := sys.stdin.readline():
+    (op, req), = json.loads(line).items()
+    try:
+        resp = {'Ok': handle(op, req)}
+    except Exception as e:
+        tb = e.__traceback__.tb_next
+        resp = {'Err': {
+            'type_name': type(e).__name__,
+            'message': str(e),
+            'traceback': 'Traceback (most recent call last):\n' + ''.join(traceback.format_tb(tb)) if tb else None,
+        }}
+    send({'Done': resp})
+"#;
+
+#[derive(Debug, Clone)]
+pub struct PoolCfg {
+    /// Interpreter processes per worker process.
+    pub n_processes: usize,
+    pub python: String,
+}
+
+impl Default for PoolCfg {
+    fn default() -> Self {
+        Self {
+            n_processes: num_cpus::get(),
+            python: "python3".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+enum Request<'a> {
+    Run {
+        f_name: &'a str,
+        script: &'a str,
+        kwargs: &'a HashMap<String, Value>,
+    },
+    Register {
+        script_id: ScriptId,
+        script: &'a str,
+    },
+    Call {
+        script_id: ScriptId,
+        f_name: &'a str,
+        kwargs: &'a HashMap<String, Value>,
+    },
+}
+
+/// What an interpreter sends while serving a request.
+#[derive(Deserialize)]
+enum Message<T> {
+    Done(Result<T, PyError>),
+    Submit(Task),
+    Await(u64),
+    Cancel(u64),
+}
+
+struct Interpreter {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    registered: HashSet<ScriptId>,
+}
+
+impl Interpreter {
+    fn spawn(python: &str) -> std::io::Result<Self> {
+        let mut child = Command::new(python)
+            .args(["-c", SERVER])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        Ok(Self {
+            stdin: child.stdin.take().unwrap(),
+            stdout: BufReader::new(child.stdout.take().unwrap()),
+            child,
+            registered: HashSet::new(),
+        })
+    }
+
+    /// Serves the `dfut` calls of the script until the request is done. The
+    /// caller's permit is released meanwhile, so a task that waits for its
+    /// children doesn't keep them from getting an interpreter.
+    fn request<T: DeserializeOwned>(
+        &mut self,
+        req: &Request<'_>,
+        caller: &mut Caller,
+    ) -> std::io::Result<Result<T, PyError>> {
+        self.send(req)?;
+        loop {
+            let mut line = String::new();
+            if self.stdout.read_line(&mut line)? == 0 {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+            let msg = serde_json::from_str(&line)?;
+            if let Message::Done(result) = msg {
+                return Ok(result);
+            }
+
+            let Caller { tasks, permits } = caller;
+            permits.add_permits(1);
+            match msg {
+                Message::Done(_) => unreachable!(),
+                Message::Submit(task) => self.send(&tasks.submit(task)),
+                Message::Await(handle) => self.send(&tasks.d_await(handle)),
+                Message::Cancel(handle) => self.send(&tasks.d_cancel(handle)),
+            }?;
+            tasks.block_on(permits.acquire()).unwrap().forget();
+        }
+    }
+
+    fn send(&mut self, msg: &impl Serialize) -> std::io::Result<()> {
+        let mut line = serde_json::to_string(msg)?;
+        line.push('\n');
+        self.stdin.write_all(line.as_bytes())?;
+        self.stdin.flush()
+    }
+}
+
+impl Drop for Interpreter {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The Python task a request runs for.
+struct Caller {
+    tasks: Tasks,
+    permits: Arc<Semaphore>,
+}
+
+/// A pool of Python interpreter subprocesses, so CPU bound Python tasks run
+/// in parallel instead of taking turns on the GIL of the worker process.
+pub struct PyPool {
+    cfg: PoolCfg,
+    permits: Arc<Semaphore>,
+    idle: Mutex<Vec<Interpreter>>,
+}
+
+impl PyPool {
+    pub fn new(cfg: PoolCfg) -> std::io::Result<Self> {
+        let idle = (0..cfg.n_processes)
+            .map(|_| Interpreter::spawn(&cfg.python))
+            .collect::<std::io::Result<_>>()?;
+        Ok(Self {
+            permits: Arc::new(Semaphore::new(cfg.n_processes)),
+            idle: Mutex::new(idle),
+            cfg,
+        })
+    }
+
+    /// Runs `f` with an idle interpreter on the blocking pool. Interpreters
+    /// that fail to answer are dropped and replaced by the next caller, and
+    /// ones started while others wait for nested tasks are dropped once
+    /// there are enough idle.
+    async fn with_interpreter<T, F>(&self, worker: Worker, f: F) -> Result<T, PyError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Interpreter, &mut Caller) -> std::io::Result<Result<T, PyError>>
+            + Send
+            + 'static,
+    {
+        let _permit = self.permits.acquire().await.unwrap();
+        let interpreter = match self.idle.lock().unwrap().pop() {
+            Some(interpreter) => Ok(interpreter),
+            None => Interpreter::spawn(&self.cfg.python),
+        };
+        let mut interpreter = interpreter.map_err(interpreter_error)?;
+
+        let mut caller = Caller {
+            tasks: Tasks::new(worker),
+            permits: Arc::clone(&self.permits),
+        };
+        let (interpreter, result) = tokio::task::spawn_blocking(move || {
+            let result = f(&mut interpreter, &mut caller);
+            (interpreter, result)
+        })
+        .await
+        .unwrap();
+
+        match result {
+            Ok(result) => {
+                let mut idle = self.idle.lock().unwrap();
+                if idle.len() < self.cfg.n_processes {
+                    idle.push(interpreter);
+                }
+                result
+            }
+            Err(e) => Err(interpreter_error(e)),
+        }
+    }
+
+    pub async fn run_py(
+        &self,
+        worker: Worker,
+        f_name: String,
+        script: String,
+        kwargs: HashMap<String, Value>,
+    ) -> Result<Value, PyError> {
+        self.with_interpreter(worker, move |interpreter, caller| {
+            interpreter.request(
+                &Request::Run {
+                    f_name: &f_name,
+                    script: &script,
+                    kwargs: &kwargs,
+                },
+                caller,
+            )
+        })
+        .await
+    }
+
+    /// Compiles `script` in one interpreter to report errors early, the others
+    /// compile it on their first call.
+    pub async fn register_script(
+        &self,
+        worker: Worker,
+        script: String,
+    ) -> Result<ScriptId, PyError> {
+        let script_id = ScriptId::of(&script);
+        self.with_interpreter(worker, move |interpreter, caller| {
+            register(interpreter, caller, script_id, &script)
+        })
+        .await
+    }
+
+    /// Compiles the script in each interpreter before its first call.
+    pub async fn call_py(
+        &self,
+        worker: Worker,
+        script: Script,
+        f_name: String,
+        kwargs: HashMap<String, Value>,
+    ) -> Result<Value, PyError> {
+        self.with_interpreter(worker, move |interpreter, caller| {
+            if !interpreter.registered.contains(&script.id()) {
+                if let Err(e) = register(interpreter, caller, script.id(), script.text())? {
+                    return Ok(Err(e));
+                }
+            }
+            interpreter.request(
+                &Request::Call {
+                    script_id: script.id(),
+                    f_name: &f_name,
+                    kwargs: &kwargs,
+                },
+                caller,
+            )
+        })
+        .await
+    }
+}
+
+fn register(
+    interpreter: &mut Interpreter,
+    caller: &mut Caller,
+    script_id: ScriptId,
+    script: &str,
+) -> std::io::Result<Result<ScriptId, PyError>> {
+    let result = interpreter.request(&Request::Register { script_id, script }, caller)?;
+    if result.is_ok() {
+        interpreter.registered.insert(script_id);
+    }
+    Ok(result)
+}
+
+fn interpreter_error(e: std::io::Error) -> PyError {
+    PyError {
+        type_name: "InterpreterError".to_string(),
+        message: e.to_string(),
+        traceback: None,
+    }
+}
+
+// Like the module cache, one pool per worker process.
+static POOL: Mutex<Option<Arc<PyPool>>> = Mutex::new(None);
+
+/// Runs the Python tasks of this process in subprocesses from now on.
+pub fn install(cfg: PoolCfg) -> std::io::Result<()> {
+    *POOL.lock().unwrap() = Some(Arc::new(PyPool::new(cfg)?));
+    Ok(())
+}
+
+/// Goes back to running Python tasks in the worker process.
+pub fn uninstall() {
+    *POOL.lock().unwrap() = None;
+}
+
+pub fn installed() -> Option<Arc<PyPool>> {
+    POOL.lock().unwrap().clone()
+}
+
+// Not a doc comment, clap would use it as the about text of every binary.
+#[derive(clap::Args, Debug, Clone, Default, Serialize)]
+pub struct PyPoolArgs {
+    /// Run Python tasks in this many interpreter subprocesses per worker
+    /// process instead of in the worker process.
+    #[arg(long)]
+    pub py_processes: Option<usize>,
+
+    /// Python executable of the subprocesses.
+    #[arg(long, default_value = "python3")]
+    pub python: String,
+}
+
+impl PyPoolArgs {
+    pub fn install(&self) {
+        if let Some(n_processes) = self.py_processes {
+            install(PoolCfg {
+                n_processes,
+                python: self.python.clone(),
+            })
+            .unwrap_or_else(|e| panic!("failed to start {}: {e}", self.python));
+        }
+    }
+}
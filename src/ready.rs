@@ -0,0 +1,216 @@
+use std::fmt;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use dfut::WorkerServerConfig;
+use tokio::net::TcpStream;
+use tokio_util::sync::CancellationToken;
+
+use crate::topology::Topology;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long [`Server::stop`] waits for blocking tasks of the server.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Returned when fewer than the wanted number of servers were listening
+/// before the timeout.
+#[derive(Debug, Clone)]
+pub struct ReadyError {
+    pub ready: usize,
+    pub want: usize,
+    pub pending: Vec<String>,
+    pub timeout: Duration,
+}
+
+impl fmt::Display for ReadyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "only {}/{} servers listening after {:?}, still waiting for {:?}",
+            self.ready, self.want, self.timeout, self.pending
+        )
+    }
+}
+
+impl std::error::Error for ReadyError {}
+
+fn host_port(address: &str) -> &str {
+    let address = address.split_once("://").map_or(address, |(_, rest)| rest);
+    address.trim_end_matches('/')
+}
+
+/// Polls `addresses` until at least `n` of them accept connections.
+pub async fn wait_listening(
+    addresses: &[String],
+    n: usize,
+    timeout: Duration,
+) -> Result<(), ReadyError> {
+    let start = Instant::now();
+    let mut pending: Vec<&String> = addresses.iter().collect();
+    loop {
+        let mut still_pending = Vec::new();
+        for address in pending {
+            if TcpStream::connect(host_port(address)).await.is_err() {
+                still_pending.push(address);
+            }
+        }
+        pending = still_pending;
+
+        let ready = addresses.len() - pending.len();
+        if ready >= n {
+            return Ok(());
+        }
+        if start.elapsed() >= timeout {
+            return Err(ReadyError {
+                ready,
+                want: n,
+                pending: pending.into_iter().cloned().collect(),
+                timeout,
+            });
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Waits until the global scheduler accepts connections.
+pub async fn wait_for_scheduler(topology: &Topology) -> Result<(), ReadyError> {
+    wait_listening(
+        std::slice::from_ref(&topology.global_scheduler_address),
+        1,
+        topology.ready_timeout(),
+    )
+    .await
+}
+
+/// Waits until `n` workers of the topology accept connections.
+///
+/// Listening only: a worker registers with the global scheduler with its
+/// first heartbeat, after it starts listening, and calls sent in between can
+/// fail. See [`wait_for_workers_registered`].
+pub async fn wait_for_workers_listening(topology: &Topology, n: u64) -> Result<(), ReadyError> {
+    wait_listening(
+        &topology.worker_addresses(),
+        n as usize,
+        topology.ready_timeout(),
+    )
+    .await
+}
+
+/// Waits until `n` workers of the topology are listening, and then for one
+/// heartbeat timeout.
+///
+/// dfut doesn't expose the scheduler's table of registered workers, nor lets
+/// a client pick the worker a call runs on, so there is no call to probe a
+/// particular worker with. A worker that is still alive after the heartbeat
+/// timeout has sent a heartbeat in it, and so registered.
+pub async fn wait_for_workers_registered(topology: &Topology, n: u64) -> Result<(), ReadyError> {
+    wait_for_workers_listening(topology, n).await?;
+    tokio::time::sleep(topology.heart_beat_timeout()).await;
+    Ok(())
+}
+
+/// A server running on a tokio runtime of its own, on its own thread.
+///
+/// Stopping it shuts that runtime down, which also stops the tasks the server
+/// spawned and closes their sockets. Aborting the `serve_forever` task alone
+/// would leave them running. Dropping a `Server` leaves it running until the
+/// process exits.
+#[derive(Debug)]
+pub struct Server {
+    stop: CancellationToken,
+    thread: std::thread::JoinHandle<()>,
+}
+
+impl Server {
+    /// Runs `serve` on a new runtime with `worker_threads` threads.
+    pub fn start<Fut>(name: &str, worker_threads: usize, serve: Fut) -> Self
+    where
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(worker_threads)
+            .thread_name(name)
+            .enable_all()
+            .build()
+            .unwrap();
+        let stop = CancellationToken::new();
+        let thread = std::thread::Builder::new()
+            .name(name.to_string())
+            .spawn({
+                let stop = stop.clone();
+                move || {
+                    runtime.spawn(serve);
+                    runtime.block_on(stop.cancelled());
+                    runtime.shutdown_timeout(SHUTDOWN_TIMEOUT);
+                }
+            })
+            .unwrap();
+        Self { stop, thread }
+    }
+
+    /// Shuts the server's runtime down and waits until it is gone.
+    pub async fn stop(self) {
+        self.stop.cancel();
+        let thread = self.thread;
+        // Err if the thread panicked, which leaves nothing to stop.
+        let _ = tokio::task::spawn_blocking(move || thread.join()).await;
+    }
+}
+
+/// The servers of an in-process cluster, see [`start_cluster`].
+#[derive(Debug)]
+pub struct InProcess {
+    pub global_scheduler: Server,
+    /// In the order of `topology.worker_addresses()`.
+    pub workers: Vec<Server>,
+}
+
+impl InProcess {
+    /// Stops the workers and the global scheduler, with the tasks they
+    /// spawned, and waits until they are gone. The next cluster can then
+    /// listen on the same addresses without a stale worker heartbeating into
+    /// its scheduler.
+    pub async fn stop(self) {
+        for worker in self.workers {
+            worker.stop().await;
+        }
+        self.global_scheduler.stop().await;
+    }
+}
+
+/// Starts the global scheduler and the workers of `topology` on runtimes of
+/// their own, and waits until all workers registered, see
+/// [`wait_for_workers_registered`].
+pub async fn start_cluster<F, Fut>(
+    topology: &Topology,
+    serve_forever: F,
+) -> Result<InProcess, ReadyError>
+where
+    F: Fn(WorkerServerConfig) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let global_scheduler = topology.spawn_global_scheduler();
+    wait_for_scheduler(topology).await?;
+
+    let workers = topology.spawn_workers(serve_forever);
+    wait_for_workers_registered(topology, topology.n_workers).await?;
+    Ok(InProcess {
+        global_scheduler,
+        workers,
+    })
+}
+
+/// Like [`start_cluster`] for a cluster that runs until the process exits,
+/// e.g. `ready::start_in_process(&topology, Worker::serve_forever)`. Returns
+/// the workers in the order of `topology.worker_addresses()`.
+pub async fn start_in_process<F, Fut>(
+    topology: &Topology,
+    serve_forever: F,
+) -> Result<Vec<Server>, ReadyError>
+where
+    F: Fn(WorkerServerConfig) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    Ok(start_cluster(topology, serve_forever).await?.workers)
+}
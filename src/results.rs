@@ -0,0 +1,169 @@
+use std::fmt;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::bench::Sample;
+use crate::now;
+use crate::topology::Topology;
+
+/// Where, when and from which commit a run was produced.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// The command line, starting with the binary.
+    pub argv: Vec<String>,
+    /// Milliseconds since the unix epoch.
+    pub started_at_ms: u64,
+    pub finished_at_ms: u64,
+    pub crate_version: String,
+    /// Unset when the binary wasn't built from a git checkout. Both are
+    /// captured at build time, see `build.rs`.
+    pub git_commit: Option<String>,
+    /// Whether the checkout had uncommitted changes.
+    pub git_dirty: Option<bool>,
+    /// The dfut version in Cargo.lock, with its source or, for a path
+    /// dependency, the commit of its checkout.
+    #[serde(default)]
+    pub dfut_version: Option<String>,
+    pub hostname: Option<String>,
+    pub n_cpus: usize,
+    pub os: String,
+    pub arch: String,
+}
+
+fn hostname() -> Option<String> {
+    let hostname = std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .or_else(|| {
+            let out = Command::new("hostname").output().ok()?;
+            Some(String::from_utf8_lossy(&out.stdout).into_owned())
+        })?;
+    Some(hostname.trim().to_string()).filter(|h| !h.is_empty())
+}
+
+impl Metadata {
+    /// Describes the current process, for a run that started at `started_at`
+    /// (see [`crate::now`]) and just finished.
+    pub fn collect(started_at: Duration) -> Self {
+        Self {
+            argv: std::env::args().collect(),
+            started_at_ms: started_at.as_millis() as u64,
+            finished_at_ms: now().as_millis() as u64,
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            git_commit: option_env!("GIT_COMMIT").map(str::to_string),
+            git_dirty: option_env!("GIT_DIRTY").map(|dirty| dirty == "true"),
+            dfut_version: option_env!("DFUT_VERSION").map(str::to_string),
+            hostname: hostname(),
+            n_cpus: num_cpus::get(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+        }
+    }
+}
+
+/// The dfut server configs a topology produces, with the values dfut uses
+/// for the settings the topology leaves unset.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClusterCfg {
+    pub topology: Topology,
+    pub global_scheduler: GlobalSchedulerValues,
+    pub workers: Vec<WorkerServerValues>,
+}
+
+/// See `dfut::GlobalSchedulerCfg`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GlobalSchedulerValues {
+    pub address: String,
+    pub heart_beat_timeout_secs: f64,
+}
+
+/// See `dfut::WorkerServerConfig`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkerServerValues {
+    pub local_server_address: String,
+    pub global_scheduler_address: String,
+}
+
+impl ClusterCfg {
+    pub fn new(topology: &Topology) -> Self {
+        let global_scheduler = topology.global_scheduler_cfg();
+        Self {
+            topology: topology.clone(),
+            global_scheduler: GlobalSchedulerValues {
+                address: global_scheduler.address,
+                heart_beat_timeout_secs: global_scheduler.heart_beat_timeout.as_secs_f64(),
+            },
+            workers: topology
+                .worker_addresses()
+                .into_iter()
+                .map(|address| {
+                    let cfg = topology.worker_server_config(address);
+                    WorkerServerValues {
+                        local_server_address: cfg.local_server_address,
+                        global_scheduler_address: cfg.global_scheduler_address,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Everything about a benchmark run: how it was configured, where it ran and
+/// its samples.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Results {
+    pub metadata: Metadata,
+    /// The parsed command line arguments of the binary.
+    pub args: serde_json::Value,
+    pub cluster: ClusterCfg,
+    pub samples: Vec<Sample>,
+}
+
+#[derive(Debug)]
+pub enum ResultsError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for ResultsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResultsError::Io(e) => write!(f, "io: {e}"),
+            ResultsError::Json(e) => write!(f, "json: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ResultsError {}
+
+impl Results {
+    /// Collects the metadata of a run of the current process that started at
+    /// `started_at`.
+    pub fn new(
+        started_at: Duration,
+        args: &impl Serialize,
+        topology: &Topology,
+        samples: Vec<Sample>,
+    ) -> Self {
+        Self {
+            metadata: Metadata::collect(started_at),
+            args: serde_json::to_value(args).unwrap(),
+            cluster: ClusterCfg::new(topology),
+            samples,
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ResultsError> {
+        let r = BufReader::new(std::fs::File::open(path).map_err(ResultsError::Io)?);
+        serde_json::from_reader(r).map_err(ResultsError::Json)
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), ResultsError> {
+        let mut w = BufWriter::new(std::fs::File::create(path).map_err(ResultsError::Io)?);
+        serde_json::to_writer(&mut w, self).map_err(ResultsError::Json)?;
+        w.flush().map_err(ResultsError::Io)
+    }
+}
@@ -0,0 +1,288 @@
+use std::fmt;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use dfut::{GlobalScheduler, GlobalSchedulerCfg, WorkerServerConfig};
+use serde::{Deserialize, Serialize};
+
+use crate::ready::Server;
+
+/// Describes an in-process or multi-process cluster. Loaded from a TOML file
+/// such as:
+///
+/// ```toml
+/// global_scheduler_address = "http://127.0.0.1:8220"
+/// heart_beat_timeout_secs = 5
+/// n_workers = 10
+/// worker_host = "127.0.0.1"
+/// worker_base_port = 8120
+/// ready_timeout_secs = 30
+/// ```
+///
+/// Missing keys take their default value. Instead of `worker_host` and
+/// `worker_base_port`, the worker addresses can be listed explicitly with
+/// `workers = ["http://10.0.0.1:8120", ...]`, and `n_workers` then defaults
+/// to their count.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Topology {
+    pub global_scheduler_address: String,
+    /// Uses the dfut default when unset.
+    pub heart_beat_timeout_secs: Option<u64>,
+    pub n_workers: u64,
+    pub worker_host: String,
+    /// Worker `i` listens on `worker_base_port + i`.
+    pub worker_base_port: u16,
+    /// Explicit worker addresses. When set, `n_workers` must be their count
+    /// and `worker_host`/`worker_base_port` are ignored.
+    pub workers: Vec<String>,
+    /// How long to wait for the cluster to come up, see [`crate::ready`].
+    pub ready_timeout_secs: u64,
+}
+
+impl Default for Topology {
+    fn default() -> Self {
+        Self {
+            global_scheduler_address: "http://127.0.0.1:8220".to_string(),
+            heart_beat_timeout_secs: None,
+            n_workers: 10,
+            worker_host: "127.0.0.1".to_string(),
+            worker_base_port: 8120,
+            workers: Vec::new(),
+            ready_timeout_secs: 30,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TopologyError {
+    Io(std::io::Error),
+    Toml(toml::de::Error),
+    /// `n_workers` differs from the number of listed `workers`.
+    WorkerCount {
+        n_workers: u64,
+        workers: usize,
+    },
+}
+
+impl fmt::Display for TopologyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TopologyError::Io(e) => write!(f, "io: {e}"),
+            TopologyError::Toml(e) => write!(f, "toml: {e}"),
+            TopologyError::WorkerCount { n_workers, workers } => write!(
+                f,
+                "n_workers = {n_workers} but {workers} workers are listed"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TopologyError {}
+
+impl Topology {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TopologyError> {
+        Self::load_over(path, Self::default())
+    }
+
+    /// Like [`Topology::load`], but keys missing from the file take their
+    /// value from `defaults`.
+    pub fn load_over(path: impl AsRef<Path>, defaults: Self) -> Result<Self, TopologyError> {
+        let s = std::fs::read_to_string(path).map_err(TopologyError::Io)?;
+        let file: toml::Table = toml::from_str(&s).map_err(TopologyError::Toml)?;
+        let lists_workers = file.contains_key("workers");
+        let sets_n_workers = file.contains_key("n_workers");
+
+        let mut table = toml::Table::try_from(defaults).unwrap();
+        table.extend(file);
+        let mut topology: Self = table.try_into().map_err(TopologyError::Toml)?;
+        if lists_workers && !sets_n_workers {
+            topology.n_workers = topology.workers.len() as u64;
+        }
+        topology.check()?;
+        Ok(topology)
+    }
+
+    /// Errors if explicit `workers` disagree with `n_workers`.
+    pub fn check(&self) -> Result<(), TopologyError> {
+        if self.workers.is_empty() || self.workers.len() as u64 == self.n_workers {
+            return Ok(());
+        }
+        Err(TopologyError::WorkerCount {
+            n_workers: self.n_workers,
+            workers: self.workers.len(),
+        })
+    }
+
+    /// A topology on free local ports, so that several clusters (e.g. one
+    /// per test) can run side by side.
+    pub fn ephemeral(n_workers: u64) -> Self {
+        // Hold on to every listener until all ports are picked so that the
+        // same port is not handed out twice.
+        let listeners: Vec<_> = (0..=n_workers)
+            .map(|_| std::net::TcpListener::bind("127.0.0.1:0").unwrap())
+            .collect();
+        let mut addresses = listeners
+            .iter()
+            .map(|l| format!("http://{}", l.local_addr().unwrap()));
+        Self {
+            global_scheduler_address: addresses.next().unwrap(),
+            n_workers,
+            workers: addresses.collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn worker_addresses(&self) -> Vec<String> {
+        if !self.workers.is_empty() {
+            return self.workers.clone();
+        }
+        (0..self.n_workers)
+            .map(|i| {
+                format!(
+                    "http://{}:{}",
+                    self.worker_host,
+                    self.worker_base_port as u64 + i
+                )
+            })
+            .collect()
+    }
+
+    pub fn ready_timeout(&self) -> Duration {
+        Duration::from_secs(self.ready_timeout_secs)
+    }
+
+    /// `heart_beat_timeout_secs`, or the dfut default.
+    pub fn heart_beat_timeout(&self) -> Duration {
+        self.global_scheduler_cfg().heart_beat_timeout
+    }
+
+    pub fn global_scheduler_cfg(&self) -> GlobalSchedulerCfg {
+        let mut cfg = GlobalSchedulerCfg {
+            address: self.global_scheduler_address.clone(),
+            ..Default::default()
+        };
+        if let Some(secs) = self.heart_beat_timeout_secs {
+            cfg.heart_beat_timeout = Duration::from_secs(secs);
+        }
+        cfg
+    }
+
+    pub fn worker_server_config(&self, local_server_address: String) -> WorkerServerConfig {
+        WorkerServerConfig {
+            local_server_address,
+            global_scheduler_address: self.global_scheduler_address.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Starts the global scheduler on a tokio runtime of its own, see
+    /// [`Server`].
+    pub fn spawn_global_scheduler(&self) -> Server {
+        Server::start(
+            "global-scheduler",
+            self.worker_threads(),
+            GlobalScheduler::serve_forever(self.global_scheduler_cfg()),
+        )
+    }
+
+    /// Threads of each in-process worker's runtime, so that together the
+    /// workers use about one per CPU like a cluster of worker processes.
+    pub fn worker_threads(&self) -> usize {
+        (num_cpus::get() / self.n_workers.max(1) as usize).max(1)
+    }
+
+    /// Starts a worker listening on `local_server_address` on a tokio runtime
+    /// of its own, see [`Server`].
+    pub fn spawn_worker<F, Fut>(&self, local_server_address: String, serve_forever: F) -> Server
+    where
+        F: Fn(WorkerServerConfig) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        Server::start(
+            "worker",
+            self.worker_threads(),
+            serve_forever(self.worker_server_config(local_server_address)),
+        )
+    }
+
+    /// Starts one worker per address, e.g.
+    /// `topology.spawn_workers(Worker::serve_forever)`.
+    pub fn spawn_workers<F, Fut>(&self, serve_forever: F) -> Vec<Server>
+    where
+        F: Fn(WorkerServerConfig) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.worker_addresses()
+            .into_iter()
+            .map(|local_server_address| self.spawn_worker(local_server_address, &serve_forever))
+            .collect()
+    }
+}
+
+// Command line flags shared by every binary. Values given on the command line
+// override the topology file, which overrides the binary's defaults. (Not a
+// doc comment, clap would use it as the about text of every binary.)
+#[derive(clap::Args, Debug, Clone, Default, Serialize)]
+pub struct TopologyArgs {
+    /// Path to a TOML topology file.
+    #[arg(long)]
+    pub topology: Option<PathBuf>,
+
+    #[arg(long)]
+    pub global_scheduler_address: Option<String>,
+
+    #[arg(long)]
+    pub heart_beat_timeout_secs: Option<u64>,
+
+    #[arg(long)]
+    pub n_workers: Option<u64>,
+
+    #[arg(long)]
+    pub worker_host: Option<String>,
+
+    #[arg(long)]
+    pub worker_base_port: Option<u16>,
+
+    #[arg(long)]
+    pub ready_timeout_secs: Option<u64>,
+}
+
+impl TopologyArgs {
+    pub fn load(&self) -> Topology {
+        self.load_with_defaults(Topology::default())
+    }
+
+    /// Panics if the topology file can't be loaded, or if the result lists
+    /// a different number of `workers` than `n_workers`.
+    pub fn load_with_defaults(&self, defaults: Topology) -> Topology {
+        let mut topology = match &self.topology {
+            Some(path) => Topology::load_over(path, defaults)
+                .unwrap_or_else(|e| panic!("failed to load topology {path:?}: {e}")),
+            None => defaults,
+        };
+        if let Some(v) = &self.global_scheduler_address {
+            topology.global_scheduler_address = v.clone();
+        }
+        if let Some(v) = self.heart_beat_timeout_secs {
+            topology.heart_beat_timeout_secs = Some(v);
+        }
+        if let Some(v) = self.n_workers {
+            topology.n_workers = v;
+        }
+        if let Some(v) = &self.worker_host {
+            topology.worker_host = v.clone();
+        }
+        if let Some(v) = self.worker_base_port {
+            topology.worker_base_port = v;
+        }
+        if let Some(v) = self.ready_timeout_secs {
+            topology.ready_timeout_secs = v;
+        }
+        if let Err(e) = topology.check() {
+            panic!("invalid topology: {e}");
+        }
+        topology
+    }
+}
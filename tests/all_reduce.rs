@@ -0,0 +1,42 @@
+use dfut_example::patterns::all_reduce::{Algorithm, ReduceOp, Worker, WorkerRootClient};
+use dfut_example::ready;
+use dfut_example::topology::Topology;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn all_reduce() {
+    let topology = Topology::ephemeral(4);
+    ready::start_in_process(&topology, Worker::serve_forever)
+        .await
+        .unwrap();
+    let root_client = WorkerRootClient::new(&topology.global_scheduler_address, "test").await;
+    let client = root_client.new_client();
+
+    // Integer valued inputs keep the sums exact regardless of combine order.
+    // A length that isn't a multiple of `n` leaves the ring chunks uneven.
+    for n in [1, 2, 3, 5] {
+        let inputs: Vec<Vec<f64>> = (0..n)
+            .map(|i| {
+                (0..1_001)
+                    .map(|j| ((i * 7 + j * 13) % 101) as f64)
+                    .collect()
+            })
+            .collect();
+        for &op in ReduceOp::ALL {
+            let want = op.local(&inputs);
+            for &algorithm in Algorithm::ALL {
+                let fut = client
+                    .all_reduce(inputs.clone(), op, algorithm)
+                    .await
+                    .unwrap();
+                let got = client.d_await(fut).await.unwrap();
+                assert_eq!(got, vec![want.clone(); n], "n={n} {op} {algorithm}");
+            }
+        }
+    }
+
+    let fut = client
+        .all_reduce(Vec::new(), ReduceOp::Sum, Algorithm::Ring)
+        .await
+        .unwrap();
+    assert!(client.d_await(fut).await.unwrap().is_empty());
+}
@@ -0,0 +1,75 @@
+use rand::seq::SliceRandom;
+
+use dfut_example::fault::{self, FaultCfg, MethodFaults};
+use dfut_example::patterns::basic::{Worker, WorkerClient, WorkerRootClient};
+use dfut_example::patterns::sort::QuickSortCfg;
+use dfut_example::ready;
+use dfut_example::topology::Topology;
+
+async fn start() -> (WorkerRootClient, WorkerClient) {
+    let topology = Topology::ephemeral(4);
+    ready::start_in_process(&topology, Worker::serve_forever)
+        .await
+        .unwrap();
+    let root_client = WorkerRootClient::new(&topology.global_scheduler_address, "test").await;
+    let client = root_client.new_client();
+    (root_client, client)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn foo_bar() {
+    let (_root_client, client) = start().await;
+
+    let mut d_futs = Vec::new();
+    for _ in 0..10 {
+        d_futs.push(client.foo_bar(2).await.unwrap());
+    }
+    for d_fut in d_futs {
+        let v = client.d_await(d_fut).await.unwrap();
+        assert_eq!(v, "hello world hello world");
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn quick_sort() {
+    let (_root_client, client) = start().await;
+
+    for blocking_partition in [true, false] {
+        let cfg = QuickSortCfg {
+            blocking_partition,
+            ..Default::default()
+        };
+        for size in [0, 1, 1_000, 400_000, 800_000] {
+            let mut v: Vec<u64> = (0..size).collect();
+            v.shuffle(&mut rand::thread_rng());
+
+            let f = client.quick_sort(v, cfg).await.unwrap();
+            let got = client.d_await(f).await.unwrap();
+            assert_eq!(got, (0..size).collect::<Vec<_>>());
+        }
+
+        // Sorted input doesn't split with the default pivot.
+        let f = client
+            .quick_sort((0..400_000).collect(), cfg)
+            .await
+            .unwrap();
+        let got = client.d_await(f).await.unwrap();
+        assert_eq!(got, (0..400_000).collect::<Vec<_>>());
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reconstruction() {
+    let (_root_client, client) = start().await;
+    fault::install(FaultCfg::default().with(
+        "retried_f",
+        MethodFaults {
+            fail_first_n: 1,
+            ..Default::default()
+        },
+    ));
+
+    let x = 42;
+    let f = client.reconstruction(x).await.unwrap();
+    assert_eq!(client.d_await(f).await.unwrap(), 2 * x);
+}
@@ -0,0 +1,42 @@
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use dfut_example::bench::{self, BenchCfg, Stop};
+
+/// Neither `setup` nor `verify` counts towards the latency of a call.
+#[tokio::test(flavor = "multi_thread")]
+async fn setup_and_verify() {
+    let cfg = BenchCfg {
+        stop: Stop::Calls(3),
+        log_every: 0,
+        ..Default::default()
+    };
+    let verified = Arc::new(AtomicU64::new(0));
+    let samples = bench::run_with_setup(
+        &cfg,
+        vec![()],
+        || {
+            std::thread::sleep(Duration::from_millis(50));
+            vec![3, 1, 2]
+        },
+        |_, mut v: Vec<u64>| async move {
+            v.sort();
+            Ok::<_, Infallible>(v)
+        },
+        {
+            let verified = Arc::clone(&verified);
+            move |v| {
+                std::thread::sleep(Duration::from_millis(50));
+                assert_eq!(v, [1, 2, 3]);
+                verified.fetch_add(1, Ordering::Relaxed);
+            }
+        },
+    )
+    .await;
+
+    assert_eq!(samples.len(), 3);
+    assert!(samples.iter().all(|s| s.dur < Duration::from_millis(50)));
+    assert_eq!(verified.load(Ordering::Relaxed), 3);
+}
@@ -0,0 +1,124 @@
+use std::time::Duration;
+
+use dfut::WorkerServerConfig;
+use dfut_example::bench::{Labels, Sample};
+use dfut_example::chaos::{self, ChaosCfg, ChaosEvent};
+use dfut_example::topology::Topology;
+use dfut_example::{ready, NoOpWorker};
+use tokio_util::sync::CancellationToken;
+
+/// Listens from a task it spawns, which outlives an aborted `serve_forever`.
+async fn serve_in_subtask(cfg: WorkerServerConfig) {
+    tokio::spawn(NoOpWorker::serve_forever(cfg));
+    std::future::pending().await
+}
+
+fn sample(t_ms: u64, ok: bool) -> Sample {
+    Sample {
+        t: Duration::from_millis(t_ms),
+        labels: Labels::new(),
+        dur: Duration::from_millis(1),
+        error: (!ok).then(|| "System".to_string()),
+        timed_out: false,
+    }
+}
+
+fn event(killed_at_ms: u64) -> ChaosEvent {
+    ChaosEvent {
+        worker: format!("worker-{killed_at_ms}"),
+        killed_at: Duration::from_millis(killed_at_ms),
+        restarted_at: Some(Duration::from_millis(killed_at_ms + 50)),
+    }
+}
+
+#[test]
+fn recoveries() {
+    let events = [event(100), event(200), event(300)];
+    let samples = [
+        sample(50, false),
+        // First kill: two errors, then a success at 160.
+        sample(110, false),
+        sample(120, true),
+        sample(130, false),
+        sample(160, true),
+        // Second kill: no errors.
+        sample(250, true),
+        // Third kill: nothing succeeds before the run ends.
+        sample(310, false),
+    ];
+    // Out of order, as the samples of several senders are.
+    let mut shuffled = samples.to_vec();
+    shuffled.reverse();
+
+    let recoveries = chaos::recoveries(&events, &shuffled);
+    let got: Vec<_> = recoveries
+        .iter()
+        .map(|r| (r.event.worker.as_str(), r.errors, r.recovered_after))
+        .collect();
+    assert_eq!(
+        got,
+        [
+            ("worker-100", 2, Some(Duration::from_millis(60))),
+            ("worker-200", 0, Some(Duration::ZERO)),
+            ("worker-300", 1, None),
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn no_workers() {
+    let topology = Topology::ephemeral(0);
+    let cfg = ChaosCfg {
+        interval: Duration::ZERO,
+        downtime: Duration::ZERO,
+        seed: 0,
+    };
+    let events = chaos::run(
+        cfg,
+        topology,
+        Vec::new(),
+        NoOpWorker::serve_forever,
+        CancellationToken::new(),
+    )
+    .await;
+    assert!(events.is_empty());
+}
+
+/// A killed worker stops listening, even from the tasks it spawned, and the
+/// restarted one listens on the same address.
+#[tokio::test(flavor = "multi_thread")]
+async fn restart() {
+    let topology = Topology::ephemeral(1);
+    let workers = ready::start_in_process(&topology, serve_in_subtask)
+        .await
+        .unwrap();
+    let cfg = ChaosCfg {
+        interval: Duration::from_millis(200),
+        downtime: Duration::from_millis(200),
+        seed: 0,
+    };
+    let address = topology.worker_addresses();
+    let ct = CancellationToken::new();
+    let chaos = tokio::spawn(chaos::run(
+        cfg,
+        topology.clone(),
+        workers,
+        serve_in_subtask,
+        ct.clone(),
+    ));
+
+    // Killed at 200ms, restarted at 400ms and killed again at 600ms.
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let listening = ready::wait_listening(&address, 1, Duration::ZERO).await;
+    assert!(listening.is_err());
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    ready::wait_listening(&address, 1, Duration::ZERO)
+        .await
+        .unwrap();
+
+    ct.cancel();
+    let events = chaos.await.unwrap();
+    assert_eq!(events.len(), 1, "{events:?}");
+    assert_eq!(events[0].worker, address[0]);
+    assert!(events[0].restarted_at.is_some());
+}
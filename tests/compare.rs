@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use dfut_example::bench::{Labels, Sample};
+use dfut_example::compare::{self, CompareCfg};
+
+/// `n` latencies spread over 10% above `base_us` microseconds.
+fn samples(labels: &Labels, base_us: u64, n: u64) -> Vec<Sample> {
+    (0..n)
+        .map(|i| Sample {
+            t: Duration::from_millis(i),
+            labels: labels.clone(),
+            dur: Duration::from_micros(base_us + (i * 7919) % (base_us / 10)),
+            error: None,
+            timed_out: false,
+        })
+        .collect()
+}
+
+#[test]
+fn quantile() {
+    let v: Vec<_> = (1..=100).map(Duration::from_millis).collect();
+    assert_eq!(compare::quantile(&v, 0.), Duration::from_millis(1));
+    assert_eq!(compare::quantile(&v, 0.5), Duration::from_millis(50));
+    assert_eq!(compare::quantile(&v, 0.99), Duration::from_millis(99));
+    assert_eq!(compare::quantile(&v, 1.), Duration::from_millis(100));
+}
+
+#[test]
+fn identical() {
+    let labels = Labels::new().with("exp", 10).with("size", 100);
+    let baseline = samples(&labels, 1_000, 500);
+    let comparison = compare::compare(&CompareCfg::default(), &baseline, &baseline);
+
+    assert_eq!(comparison.deltas.len(), 2);
+    for delta in &comparison.deltas {
+        assert_eq!(delta.change, 0.);
+        assert!(delta.ci.0 <= 0. && 0. <= delta.ci.1, "{delta}");
+        assert!(!delta.regression, "{delta}");
+    }
+}
+
+#[test]
+fn regression() {
+    let labels = Labels::new().with("exp", 10);
+    let baseline = samples(&labels, 1_000, 500);
+    let candidate = samples(&labels, 2_000, 500);
+
+    let comparison = compare::compare(&CompareCfg::default(), &baseline, &candidate);
+    assert_eq!(comparison.n_regressions(), 2);
+    for delta in &comparison.deltas {
+        assert!(delta.change > 0.9, "{delta}");
+        assert!(delta.ci.0 > 0.05, "{delta}");
+    }
+
+    // Faster is not a regression.
+    let comparison = compare::compare(&CompareCfg::default(), &candidate, &baseline);
+    assert_eq!(comparison.n_regressions(), 0);
+}
+
+#[test]
+fn alignment() {
+    let a = Labels::new().with("exp", 10).with("t", "a");
+    let b = Labels::new().with("exp", 20).with("t", "b");
+    let c = Labels::new().with("exp", 30).with("t", "c");
+    let mut baseline = samples(&a, 1_000, 100);
+    baseline.extend(samples(&b, 1_000, 100));
+    let mut candidate = samples(&b.clone().with("host", "x"), 1_000, 100);
+    candidate.extend(samples(&c, 1_000, 100));
+    candidate[0].error = Some("Timeout".to_string());
+
+    let cfg = CompareCfg {
+        keys: Some(vec!["exp".to_string()]),
+        quantiles: vec![0.5],
+        ..Default::default()
+    };
+    let comparison = compare::compare(&cfg, &baseline, &candidate);
+
+    let exp = |e: u64| Labels::new().with("exp", e);
+    assert_eq!(comparison.deltas.len(), 1);
+    let delta = &comparison.deltas[0];
+    assert_eq!(delta.labels, exp(20));
+    assert_eq!((delta.n_baseline, delta.n_candidate), (100, 99));
+    assert_eq!(comparison.only_baseline, [exp(10)]);
+    assert_eq!(comparison.only_candidate, [exp(30)]);
+    // One error in 100 is within the threshold, a missing group isn't.
+    assert_eq!(comparison.failures.len(), 1);
+    assert_eq!(comparison.failures[0].errors, (0., 0.01));
+    assert!(!comparison.failures[0].regression);
+    assert_eq!(comparison.n_regressions(), 1);
+}
+
+#[test]
+fn failures() {
+    let labels = Labels::new().with("exp", 10);
+    let baseline = samples(&labels, 1_000, 100);
+    let mut candidate = baseline.clone();
+    for (i, s) in candidate.iter_mut().enumerate() {
+        s.error = Some("Timeout".to_string());
+        s.timed_out = i % 2 == 0;
+    }
+
+    // No latencies to compare, but every call failed.
+    let comparison = compare::compare(&CompareCfg::default(), &baseline, &candidate);
+    assert!(comparison.deltas.is_empty());
+    assert!(comparison.only_baseline.is_empty());
+    assert_eq!(comparison.failures.len(), 1);
+    let delta = &comparison.failures[0];
+    assert_eq!(delta.errors, (0., 1.));
+    assert_eq!(delta.timeouts, (0., 0.5));
+    assert!(delta.regression, "{delta}");
+    assert_eq!(comparison.n_regressions(), 1);
+
+    // Fewer failures are not a regression.
+    let comparison = compare::compare(&CompareCfg::default(), &candidate, &baseline);
+    assert_eq!(comparison.n_regressions(), 0);
+}
@@ -0,0 +1,134 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use dfut::DResult;
+use dfut_example::bench::{self, BenchCfg, Stop, Summary};
+use dfut_example::deadline::{self, CallError, Deadline, Timeout};
+use dfut_example::topology::Topology;
+use dfut_example::{ready, NoOpWorker, NoOpWorkerRootClient};
+
+#[tokio::test]
+async fn within() {
+    assert_eq!(
+        deadline::within(None, async { Ok(1) }).await.unwrap(),
+        Ok(1)
+    );
+
+    let deadline = Deadline::after(Duration::from_secs(10));
+    assert!(!deadline.is_expired());
+    assert_eq!(
+        deadline::within(Some(deadline), async { Ok(1) })
+            .await
+            .unwrap(),
+        Ok(1)
+    );
+
+    let deadline = Deadline::after(Duration::from_millis(20));
+    let slow = async {
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        Ok(1)
+    };
+    assert_eq!(
+        deadline::within(Some(deadline), slow).await.unwrap(),
+        Err(Timeout)
+    );
+    assert!(deadline.is_expired());
+    assert_eq!(deadline.remaining(), Duration::ZERO);
+
+    // Not started once expired.
+    let started = std::sync::atomic::AtomicBool::new(false);
+    let f = async {
+        started.store(true, std::sync::atomic::Ordering::SeqCst);
+        Ok(1)
+    };
+    assert_eq!(
+        deadline::within(Some(deadline), f).await.unwrap(),
+        Err(Timeout)
+    );
+    assert!(!started.load(std::sync::atomic::Ordering::SeqCst));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn nop_fanout() {
+    let topology = Topology::ephemeral(2);
+    ready::start_in_process(&topology, NoOpWorker::serve_forever)
+        .await
+        .unwrap();
+    let root_client = NoOpWorkerRootClient::new(&topology.global_scheduler_address, "test").await;
+    let client = root_client.new_client();
+
+    let fut = client
+        .nop_fanout(5, 8, Some(Deadline::after(Duration::from_secs(10))))
+        .await
+        .unwrap();
+    assert_eq!(client.d_await(fut).await.unwrap(), Ok(()));
+
+    let fut = client
+        .nop_fanout(5, 8, Some(Deadline(Duration::ZERO)))
+        .await
+        .unwrap();
+    assert_eq!(client.d_await(fut).await.unwrap(), Err(Timeout));
+}
+
+/// With `backstop`, a call's own deadline passes before the runner drops it,
+/// so the call gets to cancel what it started.
+#[tokio::test(flavor = "multi_thread")]
+async fn backstop() {
+    let timeout = Some(Duration::from_millis(20));
+    let cfg = BenchCfg {
+        stop: Stop::Calls(3),
+        log_every: 0,
+        timeout: deadline::backstop(timeout),
+        ..Default::default()
+    };
+    let cancelled = Arc::new(AtomicU64::new(0));
+    let samples = bench::run(&cfg, vec![()], {
+        let cancelled = Arc::clone(&cancelled);
+        move |_| {
+            let cancelled = Arc::clone(&cancelled);
+            async move {
+                let deadline = timeout.map(Deadline::after);
+                let r = deadline::within(deadline, std::future::pending::<DResult<()>>()).await?;
+                if r.is_err() {
+                    cancelled.fetch_add(1, Ordering::Relaxed);
+                }
+                r.map_err(CallError::from)
+            }
+        }
+    })
+    .await;
+    assert_eq!(samples.len(), 3);
+    assert!(samples.iter().all(|s| s.timed_out));
+    assert_eq!(cancelled.load(Ordering::Relaxed), 3);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn bench_timeouts() {
+    let cfg = BenchCfg {
+        stop: Stop::Calls(3),
+        log_every: 0,
+        timeout: Some(Duration::from_millis(20)),
+        ..Default::default()
+    };
+
+    // Hung calls are dropped by the runner.
+    let samples = bench::run(&cfg, vec![()], |_| async {
+        std::future::pending::<()>().await;
+        Ok::<_, dfut::Error>(())
+    })
+    .await;
+    assert_eq!(samples.len(), 3);
+    assert!(samples.iter().all(|s| s.timed_out && !s.is_ok()));
+
+    // Timeouts reported by the call count too, other errors don't.
+    let samples = bench::run(&cfg, vec![()], |_| async { Err(CallError::Timeout) }).await;
+    assert!(samples.iter().all(|s| s.timed_out));
+    let samples = bench::run(&cfg, vec![()], |_| async {
+        Err(CallError::Dfut(dfut::Error::System))
+    })
+    .await;
+    assert!(samples.iter().all(|s| !s.timed_out && !s.is_ok()));
+    let summary = Summary::new(&samples);
+    assert_eq!((summary.errors, summary.timeouts), (3, 0));
+}
@@ -0,0 +1,51 @@
+use dfut_example::fault::{self, FaultCfg, MethodFaults};
+
+async fn outcomes(method: &str, n: usize) -> Vec<bool> {
+    let mut v = Vec::new();
+    for _ in 0..n {
+        v.push(fault::inject(method).await.is_ok());
+    }
+    v
+}
+
+// The injector is process wide, so everything runs in one test.
+#[tokio::test]
+async fn inject() {
+    let cfg = FaultCfg {
+        seed: 7,
+        ..Default::default()
+    }
+    .with(
+        "f",
+        MethodFaults {
+            p_fail: 0.5,
+            ..Default::default()
+        },
+    )
+    .with(
+        "g",
+        MethodFaults {
+            fail_first_n: 2,
+            ..Default::default()
+        },
+    );
+
+    // Same seed, same outcomes.
+    fault::install(cfg.clone());
+    let first = outcomes("f", 100).await;
+    fault::install(cfg.clone());
+    assert_eq!(outcomes("f", 100).await, first);
+    let n_ok = first.iter().filter(|ok| **ok).count();
+    assert!((30..70).contains(&n_ok), "n_ok={n_ok}");
+
+    // Different seed, different outcomes.
+    fault::install(FaultCfg { seed: 8, ..cfg });
+    assert_ne!(outcomes("f", 100).await, first);
+
+    // Fail first n, other methods are unaffected.
+    assert_eq!(outcomes("g", 4).await, vec![false, false, true, true]);
+    assert_eq!(outcomes("h", 2).await, vec![true, true]);
+
+    fault::uninstall();
+    assert_eq!(outcomes("f", 10).await, vec![true; 10]);
+}
@@ -0,0 +1,93 @@
+use std::convert::Infallible;
+use std::time::{Duration, Instant};
+
+use dfut_example::bench::{self, Arrivals, BenchCfg, OpenLoop, Stop, Summary};
+use tokio_util::sync::CancellationToken;
+
+fn cfg(stop: Stop) -> BenchCfg {
+    BenchCfg {
+        stop,
+        log_every: 0,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn arrivals() {
+    for a in Arrivals::ALL {
+        assert_eq!(a.to_string().parse::<Arrivals>(), Ok(*a));
+    }
+    assert!("bursty".parse::<Arrivals>().is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rate() {
+    for arrivals in Arrivals::ALL {
+        let open_loop = OpenLoop {
+            rate: 200.,
+            arrivals: *arrivals,
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let samples =
+            bench::run_open_loop(&cfg(Stop::Calls(100)), &open_loop, vec![(); 2], |_| async {
+                Ok::<_, Infallible>(())
+            })
+            .await;
+
+        assert_eq!(samples.len(), 100, "{arrivals}");
+        // 100 calls at 200/s take about half a second.
+        let elapsed = start.elapsed();
+        assert!(
+            elapsed > Duration::from_millis(250) && elapsed < Duration::from_secs(2),
+            "{arrivals}: {elapsed:?}"
+        );
+    }
+}
+
+/// Calls that queue behind a slow one count the wait, where a closed loop
+/// would only see the time each call took.
+#[tokio::test(flavor = "multi_thread")]
+async fn queueing() {
+    let open_loop = OpenLoop {
+        rate: 100.,
+        arrivals: Arrivals::Constant,
+        max_in_flight: 1,
+        ..Default::default()
+    };
+    let samples = bench::run_open_loop(&cfg(Stop::Calls(20)), &open_loop, vec![()], |_| async {
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        Ok::<_, Infallible>(())
+    })
+    .await;
+
+    let summary = Summary::new(&samples);
+    assert_eq!(summary.count, 20);
+    // Each call takes 30ms but one is due every 10ms, so the last one waited
+    // for about 20 * 20ms.
+    assert!(summary.max > Duration::from_millis(300), "{summary}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn shutdown() {
+    let ct = CancellationToken::new();
+    let cfg = BenchCfg {
+        shutdown: Some(ct.clone()),
+        ..cfg(Stop::Duration(Duration::from_secs(30)))
+    };
+    tokio::spawn({
+        let ct = ct.clone();
+        async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            ct.cancel();
+        }
+    });
+
+    let start = Instant::now();
+    let samples = bench::run_open_loop(&cfg, &OpenLoop::default(), vec![()], |_| async {
+        Ok::<_, Infallible>(())
+    })
+    .await;
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(!samples.is_empty());
+}
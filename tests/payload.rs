@@ -0,0 +1,87 @@
+use dfut_example::payload::{self, Payload, Shape};
+use dfut_example::topology::Topology;
+use dfut_example::{ready, NoOpWorker, NoOpWorkerRootClient};
+
+#[test]
+fn shapes() {
+    for shape in Shape::ALL {
+        assert_eq!(shape.to_string().parse::<Shape>(), Ok(*shape));
+
+        for n_bytes in [1 << 10, 1 << 16] {
+            let encoded = shape.payload(n_bytes).encoded_len();
+            // Off by the length prefix, or by less than one element.
+            assert!(
+                encoded.abs_diff(n_bytes) <= 100,
+                "{shape}: {encoded} for {n_bytes}"
+            );
+        }
+
+        // Smaller than one element.
+        let empty = match shape.payload(4) {
+            Payload::Bytes(v) => v.is_empty(),
+            Payload::U64s(v) => v.is_empty(),
+            Payload::Records(v) => v.is_empty(),
+            Payload::Strings(v) => v.is_empty(),
+        };
+        assert!(!empty, "{shape}");
+    }
+}
+
+#[test]
+fn costs() {
+    let points: Vec<_> = [16., 1024., 65536.]
+        .iter()
+        .map(|&x| (x, 2e-6 + 1e-9 * x))
+        .collect();
+    let (per_call, per_byte) = payload::costs(&points).unwrap();
+    assert!((per_call - 2e-6).abs() < 1e-12, "{per_call}");
+    assert!((per_byte - 1e-9).abs() < 1e-15, "{per_byte}");
+
+    assert_eq!(payload::costs(&[]), None);
+    assert_eq!(payload::costs(&[(16., 1.), (16., 2.)]), None);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn echo() {
+    let topology = Topology::ephemeral(2);
+    ready::start_in_process(&topology, NoOpWorker::serve_forever)
+        .await
+        .unwrap();
+    let root_client = NoOpWorkerRootClient::new(&topology.global_scheduler_address, "test").await;
+    let client = root_client.new_client();
+
+    for shape in [Shape::Bytes, Shape::U64s, Shape::Records, Shape::Strings] {
+        let payload = shape.payload(1 << 12);
+        let got = match payload.clone() {
+            Payload::Bytes(v) => Payload::Bytes(
+                client
+                    .d_await(client.echo_bytes(v).await.unwrap())
+                    .await
+                    .unwrap(),
+            ),
+            Payload::U64s(v) => Payload::U64s(
+                client
+                    .d_await(client.echo_u64s(v).await.unwrap())
+                    .await
+                    .unwrap(),
+            ),
+            Payload::Records(v) => Payload::Records(
+                client
+                    .d_await(client.echo_records(v).await.unwrap())
+                    .await
+                    .unwrap(),
+            ),
+            Payload::Strings(v) => Payload::Strings(
+                client
+                    .d_await(client.echo_strings(v).await.unwrap())
+                    .await
+                    .unwrap(),
+            ),
+        };
+        assert_eq!(got, payload, "{shape}");
+    }
+
+    let f = client.nop(1 << 12).await.unwrap();
+    let f = client.len_of(f).await.unwrap();
+    assert_eq!(client.d_await(f).await.unwrap(), 1 << 12);
+}
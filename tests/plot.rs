@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use dfut_example::bench::{self, Labels, Sample};
+use dfut_example::plot::{self, Metric, PlotError, Series};
+
+fn sample(t_ms: u64, labels: Labels, dur_ms: u64) -> Sample {
+    Sample {
+        t: Duration::from_millis(t_ms),
+        labels,
+        dur: Duration::from_millis(dur_ms),
+        error: None,
+        timed_out: false,
+    }
+}
+
+fn sort_samples() -> Vec<Sample> {
+    let labels = |exp_id: &str, size: u64| Labels::new().with("size", size).with("exp_id", exp_id);
+    vec![
+        sample(1_000, labels("std", 100), 10),
+        sample(1_010, labels("std", 100), 20),
+        sample(1_020, labels("std", 200), 40),
+        sample(1_500, labels("local", 200), 30),
+        sample(1_600, labels("local", 100), 5),
+    ]
+}
+
+#[test]
+fn csv_round_trip() {
+    let mut samples = sort_samples();
+    samples[1].error = Some("Timeout".to_string());
+    samples[1].timed_out = true;
+
+    let path = std::env::temp_dir().join(format!("plot-{}.csv", std::process::id()));
+    bench::write_csv(&path, &samples).unwrap();
+    let header = std::fs::read_to_string(&path).unwrap();
+    assert!(header.starts_with("t,size,exp_id,dur_s,error,timed_out\n"));
+    let loaded = bench::read_csv(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded, samples);
+}
+
+#[test]
+fn by_label() {
+    let series = vec!["exp_id".to_string()];
+    let chart = plot::by_label(&sort_samples(), "size", &series, Metric::Latency(1.)).unwrap();
+    assert_eq!(
+        chart.series,
+        [
+            Series {
+                name: "exp_id=local".to_string(),
+                points: vec![(100., 5.), (200., 30.)],
+            },
+            Series {
+                name: "exp_id=std".to_string(),
+                points: vec![(100., 20.), (200., 40.)],
+            },
+        ]
+    );
+
+    let chart = plot::by_label(&sort_samples(), "size", &[], Metric::Throughput).unwrap();
+    assert_eq!(chart.series.len(), 1);
+    assert_eq!(chart.series[0].name, "all");
+    // Three calls of size 100 between 990ms and 1600ms.
+    let (x, y) = chart.series[0].points[0];
+    assert_eq!(x, 100.);
+    assert!((y - 3. / 0.61).abs() < 1e-9, "{y}");
+
+    let err = plot::by_label(&sort_samples(), "exp_id", &[], Metric::Throughput).unwrap_err();
+    assert!(matches!(err, PlotError::Label(_)), "{err}");
+}
+
+#[test]
+fn latency_over_time() {
+    let keys = vec!["exp_id".to_string()];
+    let chart = plot::latency_over_time(
+        &sort_samples(),
+        Some(&keys),
+        0.5,
+        Duration::from_millis(500),
+    );
+    // The first call started at 990ms.
+    assert_eq!(chart.series[1].name, "exp_id=std");
+    assert_eq!(chart.series[1].points, [(0., 20.)]);
+    assert_eq!(chart.series[0].points, [(0.5, 5.)]);
+}
+
+#[test]
+fn render() {
+    let series = vec!["exp_id".to_string()];
+    let chart = plot::by_label(&sort_samples(), "size", &series, Metric::Latency(0.5)).unwrap();
+    let svg = plot::render(&chart, (800, 600)).unwrap();
+    assert!(svg.starts_with("<svg"));
+    for text in ["p50 latency (ms) by size", "exp_id=std", "exp_id=local"] {
+        assert!(svg.contains(text), "{text} missing");
+    }
+
+    // Empty charts still render.
+    let chart = plot::latency_over_time(&[], None, 0.5, Duration::from_secs(1));
+    plot::render(&chart, (800, 600)).unwrap();
+}
@@ -0,0 +1,376 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use dfut_example::patterns::py::{self, Script, Value, Worker, WorkerClient, WorkerRootClient};
+use dfut_example::ready;
+use dfut_example::topology::Topology;
+
+const SCRIPT: &str = r#"
+def echo(**kwargs):
+    return kwargs['v']
+
+def describe(**kwargs):
+    return {
+        'n': len(kwargs['items']),
+        'total': sum(kwargs['items']),
+        'mean': sum(kwargs['items']) / len(kwargs['items']),
+        'name': kwargs['name'].upper(),
+        'pair': (True, None),
+    }
+
+def divide(**kwargs):
+    return kwargs['a'] / kwargs['b']
+
+def unsupported(**kwargs):
+    return {1, 2}
+
+def floats(**kwargs):
+    v = kwargs['v']
+    return [v, type(v).__name__, -v]
+"#;
+
+async fn start() -> (WorkerRootClient, WorkerClient) {
+    let topology = Topology::ephemeral(2);
+    ready::start_in_process(&topology, Worker::serve_forever)
+        .await
+        .unwrap();
+    let root_client = WorkerRootClient::new(&topology.global_scheduler_address, "test").await;
+    let client = root_client.new_client();
+    (root_client, client)
+}
+
+fn kwargs<const N: usize>(kv: [(&str, Value); N]) -> HashMap<String, Value> {
+    kv.into_iter().map(|(k, v)| (k.to_string(), v)).collect()
+}
+
+/// NaN and the infinities reach Python as floats and come back unchanged.
+fn assert_non_finite(got: Value, v: f64) {
+    let same = |got: &Value, want: f64| match got {
+        Value::Float(got) => got.to_bits() == want.to_bits() || got.is_nan() && want.is_nan(),
+        _ => false,
+    };
+    let Value::List(got) = got else {
+        panic!("not a list: {got:?}");
+    };
+    assert!(same(&got[0], v), "{got:?}");
+    assert_eq!(got[1], Value::from("float"));
+    assert!(same(&got[2], -v), "{got:?}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn structured_values() {
+    let (_root_client, client) = start().await;
+
+    let values = [
+        Value::None,
+        Value::Bool(true),
+        Value::Int(-7),
+        Value::Float(0.25),
+        Value::from("héllo"),
+        Value::List(vec![Value::Int(1), Value::from("two"), Value::List(vec![])]),
+        Value::Dict(BTreeMap::from([
+            ("a".to_string(), Value::Float(1.5)),
+            ("b".to_string(), Value::Dict(BTreeMap::new())),
+        ])),
+    ];
+    for v in values {
+        let fut = client
+            .run_py(
+                "echo".to_string(),
+                SCRIPT.to_string(),
+                kwargs([("v", v.clone())]),
+            )
+            .await
+            .unwrap();
+        assert_eq!(client.d_await(fut).await.unwrap(), Ok(v));
+    }
+    for v in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+        let fut = client
+            .run_py(
+                "floats".to_string(),
+                SCRIPT.to_string(),
+                kwargs([("v", Value::Float(v))]),
+            )
+            .await
+            .unwrap();
+        assert_non_finite(client.d_await(fut).await.unwrap().unwrap(), v);
+    }
+
+    let fut = client
+        .run_py(
+            "describe".to_string(),
+            SCRIPT.to_string(),
+            kwargs([
+                ("items", Value::from(vec![1_i64, 2, 3, 4])),
+                ("name", Value::from("bob")),
+            ]),
+        )
+        .await
+        .unwrap();
+    let want = Value::Dict(BTreeMap::from([
+        ("n".to_string(), Value::Int(4)),
+        ("total".to_string(), Value::Int(10)),
+        ("mean".to_string(), Value::Float(2.5)),
+        ("name".to_string(), Value::from("BOB")),
+        (
+            "pair".to_string(),
+            Value::List(vec![Value::Bool(true), Value::None]),
+        ),
+    ]));
+    assert_eq!(client.d_await(fut).await.unwrap(), Ok(want));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn errors() {
+    let (_root_client, client) = start().await;
+
+    let fut = client
+        .run_py(
+            "divide".to_string(),
+            SCRIPT.to_string(),
+            kwargs([("a", Value::Int(1)), ("b", Value::Int(0))]),
+        )
+        .await
+        .unwrap();
+    let err = client.d_await(fut).await.unwrap().unwrap_err();
+    assert_eq!(err.type_name, "ZeroDivisionError");
+    assert_eq!(err.message, "division by zero");
+    let traceback = err.traceback.unwrap();
+    assert!(traceback.contains("in divide"), "{traceback}");
+
+    let fut = client
+        .run_py("unsupported".to_string(), SCRIPT.to_string(), kwargs([]))
+        .await
+        .unwrap();
+    let err = client.d_await(fut).await.unwrap().unwrap_err();
+    assert_eq!(err.type_name, "TypeError");
+    assert_eq!(err.message, "unsupported type: set");
+
+    let fut = client
+        .run_py("missing".to_string(), SCRIPT.to_string(), kwargs([]))
+        .await
+        .unwrap();
+    let err = client.d_await(fut).await.unwrap().unwrap_err();
+    assert_eq!(err.type_name, "AttributeError");
+
+    let fut = client
+        .run_py("f".to_string(), "def f(:".to_string(), kwargs([]))
+        .await
+        .unwrap();
+    let err = client.d_await(fut).await.unwrap().unwrap_err();
+    assert_eq!(err.type_name, "SyntaxError");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn cached_scripts() {
+    let (_root_client, client) = start().await;
+
+    // Like a worker process the script was never registered in.
+    let unregistered = Script::new(format!("{SCRIPT}\n# unregistered"));
+    let divide = || kwargs([("a", Value::Int(1)), ("b", Value::Int(2))]);
+    let fut = client
+        .call_py(unregistered.id(), "divide".to_string(), divide())
+        .await
+        .unwrap();
+    let err = client.d_await(fut).await.unwrap().unwrap_err();
+    assert!(err.is_unknown_script(), "{err}");
+    // The text is only sent after the miss, and the script is known since.
+    let result = py::call_by_id(&client, &unregistered, "divide", divide())
+        .await
+        .unwrap();
+    assert_eq!(result, Ok(Value::Float(0.5)));
+    let fut = client
+        .call_py(unregistered.id(), "divide".to_string(), divide())
+        .await
+        .unwrap();
+    assert_eq!(client.d_await(fut).await.unwrap(), Ok(Value::Float(0.5)));
+
+    let script = Script::new(SCRIPT);
+    for _ in 0..2 {
+        let fut = client.register_script(SCRIPT.to_string()).await.unwrap();
+        assert_eq!(client.d_await(fut).await.unwrap(), Ok(script.id()));
+    }
+
+    let fut = client
+        .call_py(
+            script.id(),
+            "divide".to_string(),
+            kwargs([("a", Value::Int(3)), ("b", Value::Int(2))]),
+        )
+        .await
+        .unwrap();
+    assert_eq!(client.d_await(fut).await.unwrap(), Ok(Value::Float(1.5)));
+
+    let fut = client
+        .call_py(
+            script.id(),
+            "divide".to_string(),
+            kwargs([("a", Value::Int(3)), ("b", Value::Int(0))]),
+        )
+        .await
+        .unwrap();
+    let err = client.d_await(fut).await.unwrap().unwrap_err();
+    assert_eq!(err.type_name, "ZeroDivisionError");
+
+    let fut = client.register_script("def f(:".to_string()).await.unwrap();
+    let err = client.d_await(fut).await.unwrap().unwrap_err();
+    assert_eq!(err.type_name, "SyntaxError");
+    let fut = client
+        .call_py(Script::new("def f(:").id(), "f".to_string(), kwargs([]))
+        .await
+        .unwrap();
+    let err = client.d_await(fut).await.unwrap().unwrap_err();
+    assert_eq!(err.type_name, "SyntaxError");
+}
+
+/// Calls keep going while another thread compiles a script whose top level
+/// code releases the GIL.
+#[tokio::test(flavor = "multi_thread")]
+async fn register_while_calling() {
+    let (root_client, client) = start().await;
+
+    let script = Script::new(SCRIPT);
+    let fut = client.register_script(SCRIPT.to_string()).await.unwrap();
+    client.d_await(fut).await.unwrap().unwrap();
+
+    let slow = "import time\ntime.sleep(1)\ndef f(**kwargs):\n    return 1\n";
+    let register = tokio::spawn({
+        let client = root_client.new_client();
+        async move {
+            let fut = client.register_script(slow.to_string()).await.unwrap();
+            client.d_await(fut).await.unwrap()
+        }
+    });
+    let calls = async {
+        for _ in 0..100 {
+            let fut = client
+                .call_py(
+                    script.id(),
+                    "echo".to_string(),
+                    kwargs([("v", Value::Int(1))]),
+                )
+                .await
+                .unwrap();
+            assert_eq!(client.d_await(fut).await.unwrap(), Ok(Value::Int(1)));
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(30), calls)
+        .await
+        .expect("calls hung");
+    let registered = tokio::time::timeout(Duration::from_secs(30), register)
+        .await
+        .expect("registration hung");
+    assert_eq!(registered.unwrap(), Ok(Script::new(slow).id()));
+}
+
+const NESTED: &str = r#"
+import dfut
+
+def quick_sort(**kwargs):
+    v = kwargs['v']
+    if len(v) <= 1:
+        return v
+    p = v[-1]
+    l = dfut.call_py(kwargs['script_id'], 'quick_sort', v=[x for x in v[:-1] if x < p], script_id=kwargs['script_id'])
+    g = dfut.call_py(kwargs['script_id'], 'quick_sort', v=[x for x in v[:-1] if x >= p], script_id=kwargs['script_id'])
+    return dfut.d_await(l) + [p] + dfut.d_await(g)
+
+def divide(**kwargs):
+    return kwargs['a'] / kwargs['b']
+
+def nested_error(**kwargs):
+    h = dfut.run_py('divide', kwargs['script'], a=1, b=0)
+    try:
+        dfut.d_await(h)
+    except dfut.TaskError as e:
+        return str(e)
+
+def unknown_script(**kwargs):
+    dfut.call_py('00000000000000ff', 'f')
+
+def abandon(**kwargs):
+    for _ in range(kwargs['n']):
+        dfut.run_py('divide', kwargs['script'], a=1, b=2)
+    dfut.d_cancel(dfut.run_py('divide', kwargs['script'], a=1, b=2))
+    return dfut.d_await(dfut.run_py('divide', kwargs['script'], a=1, b=4))
+
+def misuse(**kwargs):
+    h = dfut.register_script('def f(): pass')
+    script_id = dfut.d_await(h)
+    dfut.d_cancel(dfut.call_py(script_id, 'f'))
+    dfut.d_await(h)
+"#;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn nested_tasks() {
+    let (_root_client, client) = start().await;
+
+    let fut = client.register_script(NESTED.to_string()).await.unwrap();
+    let script_id = client.d_await(fut).await.unwrap().unwrap();
+
+    let v = vec![5_i64, 3, 9, 1, 1, 7, 2, 8, 0, 6];
+    let fut = client
+        .call_py(
+            script_id,
+            "quick_sort".to_string(),
+            kwargs([
+                ("v", Value::from(v.clone())),
+                ("script_id", Value::from(script_id.to_string())),
+            ]),
+        )
+        .await
+        .unwrap();
+    let mut want = v;
+    want.sort();
+    assert_eq!(client.d_await(fut).await.unwrap(), Ok(Value::from(want)));
+
+    let fut = client
+        .call_py(
+            script_id,
+            "nested_error".to_string(),
+            kwargs([("script", Value::from(NESTED))]),
+        )
+        .await
+        .unwrap();
+    let Ok(Value::Str(err)) = client.d_await(fut).await.unwrap() else {
+        panic!("nested_error didn't return the error");
+    };
+    assert!(
+        err.ends_with("ZeroDivisionError: division by zero"),
+        "{err}"
+    );
+    assert!(err.contains("in divide"), "{err}");
+
+    let fut = client
+        .call_py(script_id, "unknown_script".to_string(), kwargs([]))
+        .await
+        .unwrap();
+    let err = client.d_await(fut).await.unwrap().unwrap_err();
+    assert_eq!(err.type_name, "RuntimeError");
+    assert_eq!(
+        err.message,
+        "script 00000000000000ff is unknown to this worker"
+    );
+
+    // Handles that aren't awaited or cancelled are cancelled on return.
+    let abandoned = py::abandoned_tasks();
+    let fut = client
+        .call_py(
+            script_id,
+            "abandon".to_string(),
+            kwargs([("n", Value::Int(3)), ("script", Value::from(NESTED))]),
+        )
+        .await
+        .unwrap();
+    assert_eq!(client.d_await(fut).await.unwrap(), Ok(Value::Float(0.25)));
+    assert_eq!(py::abandoned_tasks() - abandoned, 3);
+
+    let fut = client
+        .call_py(script_id, "misuse".to_string(), kwargs([]))
+        .await
+        .unwrap();
+    let err = client.d_await(fut).await.unwrap().unwrap_err();
+    assert_eq!(err.type_name, "RuntimeError");
+    assert_eq!(err.message, "unknown or already awaited handle: 0");
+}
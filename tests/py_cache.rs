@@ -0,0 +1,50 @@
+//! Separate from tests/py.rs, whose scripts evicting would break its tests.
+
+use std::collections::HashMap;
+
+use dfut_example::patterns::py::{self, Script, Value, Worker, WorkerRootClient};
+use dfut_example::ready;
+use dfut_example::topology::Topology;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn evict_oldest_scripts() {
+    let topology = Topology::ephemeral(1);
+    ready::start_in_process(&topology, Worker::serve_forever)
+        .await
+        .unwrap();
+    let root_client = WorkerRootClient::new(&topology.global_scheduler_address, "test").await;
+    let client = root_client.new_client();
+
+    let scripts: Vec<_> = (0..=py::MAX_SCRIPTS)
+        .map(|i| Script::new(format!("def f(**kwargs):\n    return {i}\n")))
+        .collect();
+    for script in &scripts {
+        let fut = client
+            .register_script(script.text().to_string())
+            .await
+            .unwrap();
+        assert_eq!(client.d_await(fut).await.unwrap(), Ok(script.id()));
+    }
+
+    let newest = scripts.last().unwrap();
+    let fut = client
+        .call_py(newest.id(), "f".to_string(), HashMap::new())
+        .await
+        .unwrap();
+    assert_eq!(
+        client.d_await(fut).await.unwrap(),
+        Ok(Value::Int(py::MAX_SCRIPTS as i64))
+    );
+
+    let oldest = &scripts[0];
+    let fut = client
+        .call_py(oldest.id(), "f".to_string(), HashMap::new())
+        .await
+        .unwrap();
+    let err = client.d_await(fut).await.unwrap().unwrap_err();
+    assert!(err.is_unknown_script(), "{err}");
+    let result = py::call_by_id(&client, oldest, "f", HashMap::new())
+        .await
+        .unwrap();
+    assert_eq!(result, Ok(Value::Int(0)));
+}
@@ -0,0 +1,215 @@
+use std::collections::{BTreeMap, HashMap};
+
+use dfut_example::patterns::py::{self, Script, Value, Worker, WorkerClient, WorkerRootClient};
+use dfut_example::py_pool::{self, PoolCfg};
+use dfut_example::ready;
+use dfut_example::topology::Topology;
+
+// A separate test binary, since the pool is installed for the whole process.
+
+const SCRIPT: &str = r#"
+import os
+
+def echo(**kwargs):
+    print('echo goes to stderr')
+    return kwargs['v']
+
+def pid(**kwargs):
+    return os.getpid()
+
+def divide(**kwargs):
+    return kwargs['a'] / kwargs['b']
+
+def unsupported(**kwargs):
+    return {1, 2}
+
+def big(**kwargs):
+    return 2 ** 64
+
+def int_keys(**kwargs):
+    return {1: 2}
+
+def crash(**kwargs):
+    os._exit(1)
+
+def tree(**kwargs):
+    import dfut
+    if kwargs['depth'] == 0:
+        return 1
+    hs = [dfut.call_py(kwargs['script_id'], 'tree', depth=kwargs['depth'] - 1, script_id=kwargs['script_id']) for _ in range(2)]
+    return sum(dfut.d_await(h) for h in hs)
+
+def floats(**kwargs):
+    v = kwargs['v']
+    return [v, type(v).__name__, -v]
+"#;
+
+async fn start() -> (WorkerRootClient, WorkerClient) {
+    py_pool::install(PoolCfg {
+        n_processes: 2,
+        ..Default::default()
+    })
+    .unwrap();
+    let topology = Topology::ephemeral(2);
+    ready::start_in_process(&topology, Worker::serve_forever)
+        .await
+        .unwrap();
+    let root_client = WorkerRootClient::new(&topology.global_scheduler_address, "test").await;
+    let client = root_client.new_client();
+    (root_client, client)
+}
+
+fn kwargs<const N: usize>(kv: [(&str, Value); N]) -> HashMap<String, Value> {
+    kv.into_iter().map(|(k, v)| (k.to_string(), v)).collect()
+}
+
+/// NaN and the infinities reach Python as floats and come back unchanged.
+fn assert_non_finite(got: Value, v: f64) {
+    let same = |got: &Value, want: f64| match got {
+        Value::Float(got) => got.to_bits() == want.to_bits() || got.is_nan() && want.is_nan(),
+        _ => false,
+    };
+    let Value::List(got) = got else {
+        panic!("not a list: {got:?}");
+    };
+    assert!(same(&got[0], v), "{got:?}");
+    assert_eq!(got[1], Value::from("float"));
+    assert!(same(&got[2], -v), "{got:?}");
+}
+
+async fn run(client: &WorkerClient, f_name: &str, kwargs: HashMap<String, Value>) -> Value {
+    let fut = client
+        .run_py(f_name.to_string(), SCRIPT.to_string(), kwargs)
+        .await
+        .unwrap();
+    client.d_await(fut).await.unwrap().unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn subprocesses() {
+    let (_root_client, client) = start().await;
+
+    let values = [
+        Value::None,
+        Value::Bool(false),
+        Value::Int(i64::MIN),
+        Value::Float(-0.5),
+        Value::from("héllo"),
+        Value::List(vec![Value::Int(1), Value::List(vec![Value::None])]),
+        Value::Dict(BTreeMap::from([(
+            "a".to_string(),
+            Value::Dict(BTreeMap::new()),
+        )])),
+    ];
+    for v in values {
+        assert_eq!(run(&client, "echo", kwargs([("v", v.clone())])).await, v);
+    }
+    for v in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+        assert_non_finite(
+            run(&client, "floats", kwargs([("v", Value::Float(v))])).await,
+            v,
+        );
+    }
+
+    let Value::Int(pid) = run(&client, "pid", kwargs([])).await else {
+        panic!("pid isn't an int");
+    };
+    assert_ne!(pid, i64::from(std::process::id()));
+
+    for (f_name, type_name, message) in [
+        ("divide", "ZeroDivisionError", "division by zero"),
+        ("unsupported", "TypeError", "unsupported type: set"),
+        (
+            "big",
+            "OverflowError",
+            "Python int too large to convert to C long",
+        ),
+        (
+            "int_keys",
+            "TypeError",
+            "'int' object cannot be converted to 'PyString'",
+        ),
+    ] {
+        let fut = client
+            .run_py(
+                f_name.to_string(),
+                SCRIPT.to_string(),
+                kwargs([("a", Value::Int(1)), ("b", Value::Int(0))]),
+            )
+            .await
+            .unwrap();
+        let err = client.d_await(fut).await.unwrap().unwrap_err();
+        assert_eq!(
+            (err.type_name.as_str(), err.message.as_str()),
+            (type_name, message)
+        );
+    }
+
+    let fut = client
+        .run_py("f".to_string(), "def f(:".to_string(), kwargs([]))
+        .await
+        .unwrap();
+    let err = client.d_await(fut).await.unwrap().unwrap_err();
+    assert_eq!(err.type_name, "SyntaxError");
+
+    // A crashed interpreter is replaced.
+    let fut = client
+        .run_py("crash".to_string(), SCRIPT.to_string(), kwargs([]))
+        .await
+        .unwrap();
+    let err = client.d_await(fut).await.unwrap().unwrap_err();
+    assert_eq!(err.type_name, "InterpreterError");
+    for _ in 0..4 {
+        assert_eq!(
+            run(&client, "echo", kwargs([("v", Value::Int(1))])).await,
+            Value::Int(1)
+        );
+    }
+
+    // Scripts are registered lazily in every interpreter.
+    let script = Script::new(SCRIPT);
+    let fut = client.register_script(SCRIPT.to_string()).await.unwrap();
+    assert_eq!(client.d_await(fut).await.unwrap(), Ok(script.id()));
+    let mut futs = Vec::new();
+    for _ in 0..8 {
+        let fut = client
+            .call_py(
+                script.id(),
+                "divide".to_string(),
+                kwargs([("a", Value::Int(3)), ("b", Value::Int(2))]),
+            )
+            .await
+            .unwrap();
+        futs.push(fut);
+    }
+    for fut in futs {
+        assert_eq!(client.d_await(fut).await.unwrap(), Ok(Value::Float(1.5)));
+    }
+
+    // Deeper than the pool is large, every level waits for the next.
+    let fut = client
+        .call_py(
+            script.id(),
+            "tree".to_string(),
+            kwargs([
+                ("depth", Value::Int(4)),
+                ("script_id", Value::from(script.id().to_string())),
+            ]),
+        )
+        .await
+        .unwrap();
+    assert_eq!(client.d_await(fut).await.unwrap(), Ok(Value::Int(16)));
+
+    // Scripts that weren't registered in this process are sent on the first
+    // call, and compiled lazily in every interpreter too.
+    let unregistered = Script::new(format!("{SCRIPT}\n# unregistered"));
+    let result = py::call_by_id(
+        &client,
+        &unregistered,
+        "divide",
+        kwargs([("a", Value::Int(1)), ("b", Value::Int(2))]),
+    )
+    .await
+    .unwrap();
+    assert_eq!(result, Ok(Value::Float(0.5)));
+}
@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+use dfut::WorkerServerConfig;
+use dfut_example::topology::Topology;
+use dfut_example::{ready, NoOpWorker, NoOpWorkerRootClient};
+
+/// Listens from a task it spawns, which outlives an aborted `serve_forever`.
+async fn serve_in_subtask(cfg: WorkerServerConfig) {
+    tokio::spawn(NoOpWorker::serve_forever(cfg));
+    std::future::pending().await
+}
+
+async fn listening(addresses: &[String]) -> bool {
+    ready::wait_listening(addresses, addresses.len(), Duration::ZERO)
+        .await
+        .is_ok()
+}
+
+/// Each cluster of a sweep listens on the addresses of the one before it.
+#[tokio::test(flavor = "multi_thread")]
+async fn restart() {
+    let topology = Topology::ephemeral(3);
+    for n_workers in [3, 1, 2] {
+        let mut topology = topology.clone();
+        topology.workers.truncate(n_workers);
+        topology.n_workers = n_workers as u64;
+
+        let cluster = ready::start_cluster(&topology, NoOpWorker::serve_forever)
+            .await
+            .unwrap();
+        assert_eq!(cluster.workers.len(), n_workers);
+
+        let root_client =
+            NoOpWorkerRootClient::new(&topology.global_scheduler_address, "test").await;
+        let client = root_client.new_client();
+        let fut = client.nop_fanout(2, 4, None).await.unwrap();
+        assert_eq!(client.d_await(fut).await.unwrap(), Ok(()));
+
+        cluster.stop().await;
+        let addresses = [
+            topology.worker_addresses(),
+            vec![topology.global_scheduler_address],
+        ];
+        for address in addresses.concat() {
+            let listening = ready::wait_listening(&[address], 1, Duration::ZERO).await;
+            assert!(listening.is_err());
+        }
+    }
+}
+
+/// Stopping a cluster stops the tasks its servers spawned too.
+#[tokio::test(flavor = "multi_thread")]
+async fn stop_spawned_tasks() {
+    let topology = Topology::ephemeral(2);
+    for _ in 0..2 {
+        let cluster = ready::start_cluster(&topology, serve_in_subtask)
+            .await
+            .unwrap();
+        assert!(listening(&topology.worker_addresses()).await);
+        cluster.stop().await;
+        for address in topology.worker_addresses() {
+            assert!(!listening(&[address]).await);
+        }
+    }
+}
+
+/// Listening workers may not have registered yet, `start_cluster` waits for
+/// their first heartbeat too.
+#[tokio::test(flavor = "multi_thread")]
+async fn wait_for_registration() {
+    let topology = Topology {
+        heart_beat_timeout_secs: Some(1),
+        ..Topology::ephemeral(2)
+    };
+    let start = std::time::Instant::now();
+    let cluster = ready::start_cluster(&topology, NoOpWorker::serve_forever)
+        .await
+        .unwrap();
+    assert!(start.elapsed() >= Duration::from_secs(1));
+    cluster.stop().await;
+}
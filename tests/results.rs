@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+use dfut_example::bench::{Labels, Sample};
+use dfut_example::now;
+use dfut_example::results::Results;
+use dfut_example::topology::Topology;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct Args {
+    exp: u64,
+    modes: Vec<String>,
+}
+
+#[test]
+fn round_trip() {
+    let started_at = now();
+    let labels = Labels::new().with("size", 10).with("algo", "quick_sort");
+    let samples = vec![
+        Sample {
+            t: Duration::from_millis(1_700_000_000_123),
+            labels: labels.clone(),
+            dur: Duration::from_micros(250),
+            error: None,
+            timed_out: false,
+        },
+        Sample {
+            t: Duration::from_millis(1_700_000_000_456),
+            labels,
+            dur: Duration::from_secs(1),
+            error: Some("Timeout".to_string()),
+            timed_out: true,
+        },
+    ];
+    let args = Args {
+        exp: 20,
+        modes: vec!["cached".to_string()],
+    };
+    let topology = Topology::ephemeral(3);
+    let results = Results::new(started_at, &args, &topology, samples.clone());
+
+    let metadata = &results.metadata;
+    assert!(metadata.started_at_ms <= metadata.finished_at_ms);
+    assert!(metadata.n_cpus > 0);
+    assert!(!metadata.argv.is_empty());
+    // Built from this checkout.
+    assert!(metadata.git_commit.is_some());
+    assert!(metadata.git_dirty.is_some());
+    assert!(metadata.dfut_version.is_some());
+    assert_eq!(results.args["exp"], 20);
+    assert_eq!(results.args["modes"][0], "cached");
+    let cluster = &results.cluster;
+    assert_eq!(cluster.topology, topology);
+    assert_eq!(
+        cluster.global_scheduler.address,
+        topology.global_scheduler_address
+    );
+    assert_eq!(cluster.workers.len(), 3);
+    assert_eq!(
+        cluster.workers[1].local_server_address,
+        topology.worker_addresses()[1]
+    );
+
+    let path = std::env::temp_dir().join(format!("results-{}.json", std::process::id()));
+    results.write(&path).unwrap();
+    let loaded = Results::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded, results);
+    // Labels keep their order.
+    assert_eq!(
+        loaded.samples[0].labels.keys().collect::<Vec<_>>(),
+        ["size", "algo"]
+    );
+}
@@ -0,0 +1,76 @@
+use dfut_example::patterns::share::{
+    Dataset, HyperParams, Model, SearchCfg, Worker, WorkerClient, WorkerRootClient,
+};
+use dfut_example::ready;
+use dfut_example::topology::Topology;
+
+async fn start() -> (WorkerRootClient, WorkerClient) {
+    let topology = Topology::ephemeral(4);
+    ready::start_in_process(&topology, Worker::serve_forever)
+        .await
+        .unwrap();
+    let root_client = WorkerRootClient::new(&topology.global_scheduler_address, "test").await;
+    let client = root_client.new_client();
+    (root_client, client)
+}
+
+fn hyperparams(learning_rates: &[f64]) -> Vec<HyperParams> {
+    learning_rates
+        .iter()
+        .map(|&learning_rate| HyperParams {
+            learning_rate,
+            l2: 0.,
+        })
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn search_linear() {
+    let (_root_client, client) = start().await;
+
+    let data = Dataset::synthetic(Model::Linear, 2_000, 3, 0);
+    let cfg = SearchCfg {
+        epochs: 100,
+        n_shards: 3,
+        ..Default::default()
+    };
+    let fut = client
+        .search(Model::Linear, hyperparams(&[0.001, 0.5, 10.]), data, cfg)
+        .await
+        .unwrap();
+    let result = client.d_await(fut).await.unwrap();
+
+    assert_eq!(result.best.hyperparams.learning_rate, 0.5);
+    assert_eq!(result.best.epochs, 100);
+    // The noise is uniform in [-0.1, 0.1], half its variance is 1/600.
+    assert!(result.best.loss < 0.002, "{:?}", result.best);
+
+    // Too small a learning rate falls behind and 10 diverges, both are
+    // stopped early.
+    for trial in [&result.trials[0], &result.trials[2]] {
+        assert!(trial.stopped, "{trial:?}");
+        assert!(trial.epochs < 100);
+    }
+    assert!(!result.trials[1].stopped);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn search_logistic() {
+    let (_root_client, client) = start().await;
+
+    let data = Dataset::synthetic(Model::Logistic, 2_000, 3, 0);
+    let cfg = SearchCfg {
+        epochs: 50,
+        n_shards: 4,
+        ..Default::default()
+    };
+    let fut = client
+        .search(Model::Logistic, hyperparams(&[0.1, 1.]), data, cfg)
+        .await
+        .unwrap();
+    let result = client.d_await(fut).await.unwrap();
+
+    assert_eq!(result.best.hyperparams.learning_rate, 1.);
+    // Well below the `ln 2` of the initial zero weights.
+    assert!(result.best.loss < 0.6, "{:?}", result.best);
+}
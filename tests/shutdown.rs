@@ -0,0 +1,114 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use dfut_example::bench::{self, BenchCfg, InFlight, Stop};
+use dfut_example::deadline::CallError;
+use dfut_example::topology::Topology;
+use dfut_example::{ready, NoOpWorker, NoOpWorkerRootClient};
+use tokio_util::sync::CancellationToken;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn in_flight() {
+    let topology = Topology::ephemeral(2);
+    ready::start_in_process(&topology, NoOpWorker::serve_forever)
+        .await
+        .unwrap();
+    let root_client = NoOpWorkerRootClient::new(&topology.global_scheduler_address, "test").await;
+    let client = Arc::new(root_client.new_client());
+    let d_await = |fut| {
+        let client = Arc::clone(&client);
+        async move { client.d_await(fut).await }
+    };
+
+    let ct = CancellationToken::new();
+    let in_flight = InFlight::new(ct.clone());
+    for _ in 0..3 {
+        let fut = client.nop(4).await.unwrap();
+        let v = in_flight.d_await(fut, d_await).await;
+        assert_eq!(v.unwrap(), vec![42; 4]);
+    }
+
+    let futs = [client.nop(4).await.unwrap(), client.nop(4).await.unwrap()];
+    ct.cancel();
+    for fut in futs {
+        let v = in_flight.d_await(fut, d_await).await;
+        assert!(matches!(v, Err(CallError::Cancelled)), "{v:?}");
+    }
+    assert_eq!((in_flight.completed(), in_flight.cancelled()), (3, 2));
+    assert_eq!(in_flight.to_string(), "completed=3 cancelled=2");
+}
+
+/// Shutdown doesn't wait for a `d_await` that already started, which still
+/// collects the result.
+#[tokio::test(flavor = "multi_thread")]
+async fn in_flight_slow() {
+    let topology = Topology::ephemeral(2);
+    ready::start_in_process(&topology, NoOpWorker::serve_forever)
+        .await
+        .unwrap();
+    let root_client = NoOpWorkerRootClient::new(&topology.global_scheduler_address, "test").await;
+    let client = Arc::new(root_client.new_client());
+
+    let ct = CancellationToken::new();
+    let in_flight = InFlight::new(ct.clone());
+    tokio::spawn({
+        let ct = ct.clone();
+        async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            ct.cancel();
+        }
+    });
+    let fut = client.nop(4).await.unwrap();
+    let (done, collected) = tokio::sync::oneshot::channel();
+    let slow = {
+        let client = Arc::clone(&client);
+        |fut| async move {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            let v = client.d_await(fut).await;
+            let _ = done.send(());
+            v
+        }
+    };
+    let started = std::time::Instant::now();
+    let v = in_flight.d_await(fut, slow).await;
+    assert!(matches!(v, Err(CallError::Cancelled)), "{v:?}");
+    assert!(started.elapsed() < Duration::from_millis(400));
+    assert_eq!((in_flight.completed(), in_flight.cancelled()), (0, 1));
+    collected.await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn bench_shutdown() {
+    let ct = CancellationToken::new();
+    let cfg = BenchCfg {
+        stop: Stop::Duration(Duration::from_secs(60)),
+        log_every: 0,
+        shutdown: Some(ct.clone()),
+        ..Default::default()
+    };
+    tokio::spawn({
+        let ct = ct.clone();
+        async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            ct.cancel();
+        }
+    });
+    let samples = bench::run(&cfg, vec![(), ()], {
+        let ct = ct.clone();
+        move |_| {
+            let ct = ct.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                if ct.is_cancelled() {
+                    return Err(CallError::Cancelled);
+                }
+                Ok(())
+            }
+        }
+    })
+    .await;
+
+    // Stopped long before the duration, without recording cancelled calls.
+    assert!(!samples.is_empty());
+    assert!(samples.iter().all(|s| s.is_ok()));
+}
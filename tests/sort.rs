@@ -0,0 +1,103 @@
+use dfut_example::patterns::sort::{
+    self, local_quick_sort, partition_or_sort, Distribution, Pivot, QuickSortCfg, Split, Worker,
+    WorkerClient, WorkerRootClient,
+};
+use dfut_example::ready;
+use dfut_example::topology::Topology;
+
+async fn start() -> (WorkerRootClient, WorkerClient) {
+    let topology = Topology::ephemeral(4);
+    ready::start_in_process(&topology, Worker::serve_forever)
+        .await
+        .unwrap();
+    let root_client = WorkerRootClient::new(&topology.global_scheduler_address, "test").await;
+    let client = root_client.new_client();
+    (root_client, client)
+}
+
+fn sorted(v: &[u64]) -> Vec<u64> {
+    let mut v = v.to_vec();
+    v.sort_unstable();
+    v
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn quick_sort_cfgs() {
+    let (_root_client, client) = start().await;
+
+    for &pivot in Pivot::ALL {
+        for blocking_partition in [true, false] {
+            let cfg = QuickSortCfg {
+                cutoff: 100,
+                pivot,
+                blocking_partition,
+            };
+            for dist in Distribution::ALL {
+                let v = dist.generate(2_000);
+                let want = sorted(&v);
+                assert_eq!(local_quick_sort(v.clone(), &cfg), want, "{cfg:?} {dist}");
+
+                let f = client.quick_sort(v, cfg).await.unwrap();
+                assert_eq!(client.d_await(f).await.unwrap(), want, "{cfg:?} {dist}");
+            }
+        }
+    }
+}
+
+/// With the last element as pivot, sorted and reverse sorted inputs don't
+/// split, and the default cutoff would recurse once per element.
+#[tokio::test(flavor = "multi_thread")]
+async fn degenerate_partitions() {
+    let (_root_client, client) = start().await;
+
+    let sorted_splits = sort::sorted_splits();
+    assert_eq!(
+        partition_or_sort(vec![3, 1, 2, 4], Pivot::Last),
+        Split::Sorted(vec![1, 2, 3, 4])
+    );
+    assert!(sort::sorted_splits() > sorted_splits);
+    assert_eq!(
+        partition_or_sort(vec![3, 4, 1, 2], Pivot::Last),
+        Split::Partitioned(vec![1], 2, vec![3, 4])
+    );
+
+    let cfg = QuickSortCfg::default();
+    for dist in [Distribution::Sorted, Distribution::Reverse] {
+        let v = dist.generate(1_000_000);
+        let want = sorted(&v);
+        assert_eq!(local_quick_sort(v.clone(), &cfg), want, "{dist}");
+
+        let f = client.quick_sort(v, cfg).await.unwrap();
+        assert_eq!(client.d_await(f).await.unwrap(), want, "{dist}");
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn merge_sort() {
+    let (_root_client, client) = start().await;
+
+    for (size, cutoff) in [(0, 100), (1, 100), (1_000, 100), (500_000, 200_000)] {
+        for dist in Distribution::ALL {
+            let v = dist.generate(size);
+            let want = sorted(&v);
+
+            let f = client.merge_sort(v, cutoff).await.unwrap();
+            assert_eq!(client.d_await(f).await.unwrap(), want, "{dist} {size}");
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sample_sort() {
+    let (_root_client, client) = start().await;
+
+    for (size, cutoff) in [(0, 100), (1, 100), (1_000, 100), (500_000, 200_000)] {
+        for dist in Distribution::ALL {
+            let v = dist.generate(size);
+            let want = sorted(&v);
+
+            let f = client.sample_sort(v, 4, cutoff).await.unwrap();
+            assert_eq!(client.d_await(f).await.unwrap(), want, "{dist} {size}");
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use std::path::PathBuf;
+
+use dfut_example::topology::{Topology, TopologyArgs, TopologyError};
+
+fn write(name: &str, toml: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("topology-{name}-{}.toml", std::process::id()));
+    std::fs::write(&path, toml).unwrap();
+    path
+}
+
+#[test]
+fn file_over_defaults() {
+    let path = write("partial", "n_workers = 3\n");
+    let args = TopologyArgs {
+        topology: Some(path.clone()),
+        worker_base_port: Some(9000),
+        ..Default::default()
+    };
+    let topology = args.load_with_defaults(Topology {
+        heart_beat_timeout_secs: Some(20),
+        n_workers: 7,
+        ..Default::default()
+    });
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(
+        topology,
+        Topology {
+            heart_beat_timeout_secs: Some(20),
+            n_workers: 3,
+            worker_base_port: 9000,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn listed_workers() {
+    let workers = r#"workers = ["http://127.0.0.1:9000", "http://127.0.0.1:9001"]"#;
+    let path = write("workers", &format!("{workers}\n"));
+    let topology = Topology::load_over(&path, Topology::default()).unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(topology.n_workers, 2);
+
+    let path = write("mismatch", &format!("n_workers = 3\n{workers}\n"));
+    let err = Topology::load(&path).unwrap_err();
+    std::fs::remove_file(path).unwrap();
+    assert!(
+        matches!(
+            err,
+            TopologyError::WorkerCount {
+                n_workers: 3,
+                workers: 2
+            }
+        ),
+        "{err}"
+    );
+}
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+
+use dfut_example::patterns::word_count::{
+    local_word_count, partition_of, read_shards, Combiner, Worker, WorkerRootClient,
+};
+use dfut_example::ready;
+use dfut_example::topology::Topology;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn word_count() {
+    let topology = Topology::ephemeral(4);
+    ready::start_in_process(&topology, Worker::serve_forever)
+        .await
+        .unwrap();
+    let root_client = WorkerRootClient::new(&topology.global_scheduler_address, "test").await;
+    let client = root_client.new_client();
+
+    let dir = std::env::temp_dir().join(format!("word-count-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("a.txt"), "The quick brown fox.\nthe lazy dog!").unwrap();
+    std::fs::write(dir.join("b.txt"), "Dog eat dog, fox-trot; THE end").unwrap();
+    std::fs::write(dir.join("c.txt"), "").unwrap();
+    let shards = read_shards(&dir).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let want: HashMap<String, u64> = [
+        ("the", 3),
+        ("quick", 1),
+        ("brown", 1),
+        ("fox", 2),
+        ("lazy", 1),
+        ("dog", 3),
+        ("eat", 1),
+        ("trot", 1),
+        ("end", 1),
+    ]
+    .into_iter()
+    .map(|(w, c)| (w.to_string(), c))
+    .collect();
+    assert_eq!(local_word_count(&shards), want);
+
+    for &combiner in Combiner::ALL {
+        for n_partitions in [1, 3, 16] {
+            let f = client
+                .word_count(shards.clone(), n_partitions, combiner)
+                .await
+                .unwrap();
+            let got = client.d_await(f).await.unwrap();
+            assert_eq!(got, want, "{combiner} n_partitions={n_partitions}");
+        }
+    }
+}
+
+/// Workers built by different Rust releases must agree on the partitions.
+#[test]
+fn stable_partitions() {
+    for (word, want) in [("the", 12), ("dfut", 14), ("word", 13)] {
+        assert_eq!(partition_of(word, 16), want, "{word}");
+    }
+}